@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::GodboltResponse;
+
+/// One line of assembly output
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct AsmResult {
+    pub text : Option<String>,
+    /// Source location the line was generated from, absent for directives and labels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source : Option<AsmSource>,
+    /// Labels referenced on the line (i.e. the target of a jump or call)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels : Vec<AsmLabel>,
+    /// Instruction bytes in hex, with the binary filter
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub opcodes : Vec<String>,
+    /// Instruction address, with the binary filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address : Option<u64>,
+}
+
+/// Where in the sources an assembly line comes from
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct AsmSource {
+    /// File the line is in; `None` for the main source
+    #[serde(default)]
+    pub file : Option<String>,
+    /// One-based line number
+    #[serde(default)]
+    pub line : Option<u32>,
+    /// One-based column, when the compiler emits it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column : Option<u32>,
+    /// Whether `file` is the main source, for compilers that name it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mainsource : Option<bool>,
+}
+
+impl AsmSource {
+    /// Whether the location is in the main source rather than a header or another file
+    pub fn is_main(&self) -> bool {
+        self.mainsource.unwrap_or(self.file.is_none())
+    }
+}
+
+/// A label referenced by an assembly line
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct AsmLabel {
+    pub name : String,
+    /// Where the name appears in the line's text
+    pub range : LabelRange,
+}
+
+/// One-based column range of a label in a line, end exclusive
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct LabelRange {
+    #[serde(rename = "startCol")]
+    pub start_col : u32,
+    #[serde(rename = "endCol")]
+    pub end_col : u32,
+}
+
+impl AsmResult {
+    /// The line's text, empty if the compiler sent none
+    pub fn text(&self) -> &str {
+        self.text.as_deref().unwrap_or("")
+    }
+
+    /// Line of the main source this was generated from
+    pub fn source_line(&self) -> Option<u32> {
+        match &self.source {
+            Some(source) if source.is_main() => source.line,
+            _ => None,
+        }
+    }
+
+    /// Name of the label this line defines (`main:` defines `main`). Demangled names may
+    /// hold spaces (`sum(int*, int):`); instructions are told apart by their indentation.
+    /// Trailing comments, as clang writes them (`main:   # @main`), are ignored.
+    pub fn label_definition(&self) -> Option<&str> {
+        let text = self.text();
+        if text.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let name = strip_comment(text).trim_end().strip_suffix(':')?;
+        if name.is_empty() {
+            return None;
+        }
+        Some(name.trim_matches('"'))
+    }
+
+    /// The label referenced at a one-based column, i.e. where a user clicked
+    pub fn label_at(&self, column : u32) -> Option<&AsmLabel> {
+        self.labels.iter().find(|l| l.range.start_col <= column && column < l.range.end_col)
+    }
+}
+
+impl GodboltResponse {
+    /// Assembly lines, empty when assembly was skipped
+    pub fn asm_lines(&self) -> &[AsmResult] {
+        self.asm.as_deref().unwrap_or(&[])
+    }
+
+    /// Index into [`GodboltResponse::asm_lines`] of the line defining `label`
+    pub fn label_target(&self, label : &str) -> Option<usize> {
+        if let Some(&line) = self.label_definitions.get(label) {
+            return line.checked_sub(1).filter(|&i| i < self.asm_lines().len());
+        }
+        self.asm_lines().iter().position(|l| l.label_definition() == Some(label))
+    }
+
+    /// Indexes of the assembly lines generated from a line of the main source
+    pub fn asm_for_source_line(&self, line : u32) -> Vec<usize> {
+        self.asm_lines().iter().enumerate()
+            .filter(|(_, l)| l.source_line() == Some(line))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Main source lines mapped to the indexes of the assembly lines generated from them
+    pub fn source_map(&self) -> BTreeMap<u32, Vec<usize>> {
+        let mut map : BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        for (i, line) in self.asm_lines().iter().enumerate() {
+            if let Some(source) = line.source_line() {
+                map.entry(source).or_default().push(i);
+            }
+        }
+        map
+    }
+}
+
+/// Drops `#`, `;` and `//` comments. `#` followed by a value is an AArch64 immediate.
+pub(crate) fn strip_comment(text : &str) -> &str {
+    let mut end = text.len();
+    if let Some(i) = text.find("//") {
+        end = end.min(i);
+    }
+    if let Some(i) = text.find(';') {
+        end = end.min(i);
+    }
+    for (i, _) in text.match_indices('#') {
+        if text[i + 1..].chars().next().is_none_or(char::is_whitespace) || text[..i].trim().is_empty() {
+            end = end.min(i);
+            break;
+        }
+    }
+    &text[..end]
+}
+
+/// Prefixes of labels the compiler makes up: ELF's `.L` and the Mach-O ones (`LBB0_2`,
+/// `Lfunc_end0`, `LCPI0_0`, `l_.str`)
+const LOCAL_LABEL_PREFIXES : [&str; 10] = [".L", "LBB", "Ltmp", "Lfunc_", "LCPI", "LJTI", "Lloh", "Lset", "L_", "l_"];
+
+/// Labels the compiler made up for jump targets and constants, rather than symbols
+pub(crate) fn is_local_label(name : &str) -> bool {
+    LOCAL_LABEL_PREFIXES.iter().any(|p| name.starts_with(p))
+}
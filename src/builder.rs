@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT};
+use reqwest::Proxy;
+use tokio::sync::Semaphore;
+
+use crate::{Godbolt, GodboltError, GodboltSnapshot, RetryPolicy, DEFAULT_BASE_URL};
+use crate::limiter::RateLimiter;
+
+/// User agent sent when none is configured
+pub const DEFAULT_USER_AGENT : &str = "godbolt-rust-crate";
+
+/// Configures the HTTP client shared by every call made through a [`Godbolt`]
+pub struct GodboltBuilder {
+    base_url : String,
+    user_agent : String,
+    connect_timeout : Option<Duration>,
+    timeout : Option<Duration>,
+    pool_idle_timeout : Option<Duration>,
+    pool_max_idle_per_host : Option<usize>,
+    proxies : Vec<Proxy>,
+    headers : Vec<(String, String)>,
+    retry : RetryPolicy,
+    rate_limit : Option<(f64, u32)>,
+    max_in_flight : Option<usize>,
+}
+
+impl Default for GodboltBuilder {
+    fn default() -> Self {
+        GodboltBuilder {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            connect_timeout: None,
+            timeout: None,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            proxies: Vec::new(),
+            headers: Vec::new(),
+            retry: RetryPolicy::none(),
+            rate_limit: None,
+            max_in_flight: None,
+        }
+    }
+}
+
+impl GodboltBuilder {
+    pub fn new() -> Self {
+        GodboltBuilder::default()
+    }
+
+    /// Base URL of the Compiler Explorer instance.
+    /// Path prefixes are kept, so `https://ce.corp/explorer/` is a valid base.
+    pub fn base_url(mut self, base_url : &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    /// User agent sent with every request
+    pub fn user_agent(mut self, user_agent : &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Time allowed to establish a connection
+    pub fn connect_timeout(mut self, timeout : Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Time allowed for a whole request, from connecting until the response body is read
+    pub fn timeout(mut self, timeout : Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// How long idle pooled connections are kept alive
+    pub fn pool_idle_timeout(mut self, timeout : Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum number of idle pooled connections kept per host
+    pub fn pool_max_idle_per_host(mut self, max : usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Routes requests through a proxy. May be called more than once.
+    pub fn proxy(mut self, proxy : Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Adds a header sent with every request
+    pub fn header(mut self, name : &str, value : &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Retries failed requests on every endpoint according to `policy`
+    pub fn retry_policy(mut self, policy : RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Starts at most `per_second` requests per second, allowing short bursts of
+    /// up to `burst` requests. Calls over budget wait rather than fail.
+    pub fn rate_limit(mut self, per_second : f64, burst : u32) -> Self {
+        self.rate_limit = Some((per_second, burst));
+        self
+    }
+
+    /// Keeps at most `max` requests in flight at once; further calls wait for a slot
+    pub fn max_in_flight(mut self, max : usize) -> Self {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Builds the client and loads the language and compiler cache
+    pub async fn build(self) -> Result<Godbolt, GodboltError> {
+        let mut instance = self.build_uncached()?;
+        instance.load_cache().await?;
+        Ok(instance)
+    }
+
+    /// Builds the client and fills the cache from a snapshot on disk.
+    /// See [`Godbolt::from_snapshot`].
+    pub async fn build_from_snapshot<P : AsRef<Path>>(self, path : P, ttl : Duration) -> Result<Godbolt, GodboltError> {
+        let path = path.as_ref();
+        let mut instance = self.build_uncached()?;
+
+        // A missing or unreadable snapshot, or one of some other instance, is as good as none
+        match GodboltSnapshot::load(path) {
+            Ok(snapshot) if snapshot.base_url == instance.base_url => {
+                let stale = snapshot.is_stale(ttl);
+                instance.apply_snapshot(snapshot);
+                if stale {
+                    instance.spawn_refresh(path);
+                }
+            }
+            _ => {
+                instance.load_cache().await?;
+                instance.save_snapshot(path)?;
+            }
+        }
+        Ok(instance)
+    }
+
+    /// Builds the client without touching the network; the cache starts out empty
+    pub(crate) fn build_uncached(self) -> Result<Godbolt, GodboltError> {
+        if let Err(e) = reqwest::Url::parse(&self.base_url) {
+            return Err(GodboltError::Config(format!("base URL `{}`: {}", self.base_url, e)));
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        for (name, value) in &self.headers {
+            let header = match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                (Ok(name), Ok(value)) => (name, value),
+                _ => return Err(GodboltError::Config(format!("header `{}: {}`", name, value))),
+            };
+            headers.insert(header.0, header.1);
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .user_agent(self.user_agent)
+            .default_headers(headers);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some((per_second, _)) = self.rate_limit {
+            if !(per_second.is_finite() && per_second > 0.0) {
+                return Err(GodboltError::Config(format!("rate limit of {} requests per second", per_second)));
+            }
+        }
+        if !(self.retry.multiplier.is_finite() && self.retry.multiplier >= 0.0) {
+            return Err(GodboltError::Config(format!("retry backoff multiplier of {}", self.retry.multiplier)));
+        }
+        if self.max_in_flight == Some(0) {
+            return Err(GodboltError::Config("at least one request must be allowed in flight".to_string()));
+        }
+
+        for proxy in self.proxies {
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(Godbolt {
+            cache: Vec::new(),
+            formats: Vec::new(),
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            client: match builder.build() {
+                Ok(client) => client,
+                Err(e) => return Err(GodboltError::Config(e.to_string())),
+            },
+            retry: self.retry,
+            limiter: self.rate_limit.map(|(per_second, burst)| Arc::new(RateLimiter::new(per_second, burst))),
+            in_flight: self.max_in_flight.map(|max| Arc::new(Semaphore::new(max))),
+            refresh: None,
+            compiler_index: HashMap::new(),
+            language_index: HashMap::new(),
+        })
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{AsmResult, GodboltError, GodboltResponse};
+use crate::asm::{is_local_label, strip_comment};
+
+/// Instruction sets whose branches [`Cfg`] understands
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Arch {
+    X86_64,
+    AArch64,
+}
+
+impl Arch {
+    /// From a compiler's [`Compiler::instruction_set`](crate::Compiler::instruction_set)
+    pub fn from_instruction_set(instruction_set : &str) -> Option<Arch> {
+        match instruction_set.to_lowercase().as_str() {
+            "amd64" | "x86" | "x86_64" | "x86-64" => Some(Arch::X86_64),
+            "aarch64" | "arm64" => Some(Arch::AArch64),
+            _ => None,
+        }
+    }
+
+    /// Guesses the instruction set from mnemonics only AArch64 has, else x86-64
+    pub fn detect(asm : &[AsmResult]) -> Arch {
+        let aarch64 = asm.iter().any(|line| {
+            let mnemonic = mnemonic(strip_comment(line.text()));
+            matches!(mnemonic, "adrp" | "bl" | "b" | "cbz" | "cbnz" | "tbz" | "tbnz" | "stp" | "ldp") || mnemonic.starts_with("b.")
+        });
+        if aarch64 { Arch::AArch64 } else { Arch::X86_64 }
+    }
+}
+
+/// Control-flow graphs of the functions in an assembly listing
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Cfg {
+    pub arch : Arch,
+    pub functions : Vec<CfgFunction>,
+}
+
+/// A function, split into basic blocks
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CfgFunction {
+    pub name : String,
+    /// In listing order; the first is the entry
+    pub blocks : Vec<BasicBlock>,
+    pub edges : Vec<CfgEdge>,
+}
+
+/// Instructions that run one after the other, entered only at the top
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Index into [`CfgFunction::blocks`]
+    pub id : usize,
+    /// Label the block starts at, if any
+    pub label : Option<String>,
+    /// Range of the block in the listing, as indexes of the assembly lines
+    pub start : usize,
+    pub end : usize,
+    /// Instruction texts, comments stripped and spacing collapsed
+    pub instructions : Vec<String>,
+}
+
+/// A possible transfer of control between two blocks
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+pub struct CfgEdge {
+    pub from : usize,
+    pub to : usize,
+    pub kind : EdgeKind,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    /// Unconditional jump
+    Jump,
+    /// Conditional branch taken
+    Taken,
+    /// Conditional branch not taken, falling through to the next block
+    NotTaken,
+    /// The block ends at a label and runs into the next one
+    Fallthrough,
+}
+
+impl EdgeKind {
+    /// Colours Compiler Explorer's CFG view uses
+    fn colour(self) -> &'static str {
+        match self {
+            EdgeKind::Taken => "green",
+            EdgeKind::NotTaken => "red",
+            EdgeKind::Jump | EdgeKind::Fallthrough => "blue",
+        }
+    }
+}
+
+/// How a block's last instruction hands on control
+enum Terminator<'a> {
+    /// Not a branch; the next block follows
+    None,
+    /// Unconditional jump to a label; indirect jumps name no label and leave the graph
+    Jump(&'a str),
+    Conditional(&'a str),
+    /// Returns, traps or jumps somewhere unknown
+    Exit,
+}
+
+impl GodboltResponse {
+    /// Control-flow graph of this response's assembly. Needs labels kept and directives
+    /// filtered, as Compiler Explorer's CFG view does.
+    pub fn cfg(&self, arch : Arch) -> Cfg {
+        Cfg::new(self.asm_lines(), arch)
+    }
+}
+
+impl Cfg {
+    /// Splits `asm` into functions at each symbol label, then into basic blocks at local
+    /// labels and branches
+    pub fn new(asm : &[AsmResult], arch : Arch) -> Cfg {
+        let mut functions = Vec::new();
+        let mut current : Option<(String, usize)> = None;
+        for (i, line) in asm.iter().enumerate() {
+            if let Some(name) = line.label_definition().filter(|n| !is_local_label(n)) {
+                if let Some((name, start)) = current.take() {
+                    functions.push(CfgFunction::new(name, asm, start, i, arch));
+                }
+                current = Some((name.to_string(), i + 1));
+            }
+        }
+        if let Some((name, start)) = current {
+            functions.push(CfgFunction::new(name, asm, start, asm.len(), arch));
+        }
+        Cfg { arch, functions }
+    }
+
+    /// A function by name
+    pub fn function(&self, name : &str) -> Option<&CfgFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    pub fn to_json(&self) -> Result<String, GodboltError> {
+        match serde_json::to_string(self) {
+            Ok(json) => Ok(json),
+            Err(e) => Err(GodboltError::Serialize(e)),
+        }
+    }
+
+    /// Graphviz source drawing each function as a cluster of blocks
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (f, function) in self.functions.iter().enumerate() {
+            out.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", f, escape(&function.name)));
+            for block in &function.blocks {
+                let mut label = String::new();
+                if let Some(name) = &block.label {
+                    label.push_str(&format!("{}:\\l", escape(name)));
+                }
+                for instruction in &block.instructions {
+                    label.push_str(&format!("    {}\\l", escape(instruction)));
+                }
+                out.push_str(&format!("        f{}b{} [label=\"{}\"];\n", f, block.id, label));
+            }
+            for edge in &function.edges {
+                out.push_str(&format!("        f{}b{} -> f{}b{} [color={}];\n", f, edge.from, f, edge.to, edge.kind.colour()));
+            }
+            out.push_str("    }\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl CfgFunction {
+    fn new(name : String, asm : &[AsmResult], start : usize, end : usize, arch : Arch) -> CfgFunction {
+        let mut blocks : Vec<BasicBlock> = Vec::new();
+        let mut labels : HashMap<String, usize> = HashMap::new();
+        let mut terminators = Vec::new();
+        // Whether the next instruction starts a block
+        let mut leader = true;
+
+        for (i, line) in asm.iter().enumerate().take(end).skip(start) {
+            if let Some(label) = line.label_definition() {
+                // Labels in a row name the same block
+                let labelled = blocks.last().is_some_and(|b| b.instructions.is_empty());
+                if !labelled {
+                    blocks.push(BasicBlock { id: blocks.len(), label: Some(label.to_string()), start: i, end: i + 1, instructions: Vec::new() });
+                    terminators.push(Terminator::None);
+                }
+                labels.insert(label.to_string(), blocks.len() - 1);
+                leader = false;
+                continue;
+            }
+            let code = strip_comment(line.text()).trim();
+            if code.is_empty() || code.starts_with('.') {
+                continue;
+            }
+            if leader {
+                blocks.push(BasicBlock { id: blocks.len(), label: None, start: i, end: i + 1, instructions: Vec::new() });
+                terminators.push(Terminator::None);
+            }
+            let block = blocks.last_mut().unwrap();
+            block.instructions.push(code.split_whitespace().collect::<Vec<_>>().join(" "));
+            block.end = i + 1;
+            let terminator = terminator(code, arch);
+            leader = !matches!(terminator, Terminator::None);
+            *terminators.last_mut().unwrap() = terminator;
+        }
+
+        // Labels after the last instruction (Mach-O's `Lfunc_end0`) mark where the function ends
+        while blocks.last().is_some_and(|b| b.instructions.is_empty()) {
+            blocks.pop();
+            terminators.pop();
+        }
+        labels.retain(|_, block| *block < blocks.len());
+
+        let mut edges = Vec::new();
+        for (from, terminator) in terminators.iter().enumerate() {
+            let next = Some(from + 1).filter(|&n| n < blocks.len());
+            let (target, kind, falls_through) = match terminator {
+                Terminator::None => (None, EdgeKind::Fallthrough, true),
+                Terminator::Jump(target) => (labels.get(*target), EdgeKind::Jump, false),
+                Terminator::Conditional(target) => (labels.get(*target), EdgeKind::Taken, true),
+                Terminator::Exit => (None, EdgeKind::Jump, false),
+            };
+            // Jumps to other functions are tail calls and leave the graph
+            if let Some(&to) = target {
+                edges.push(CfgEdge { from, to, kind });
+            }
+            if let Some(to) = next.filter(|_| falls_through) {
+                let kind = if kind == EdgeKind::Taken { EdgeKind::NotTaken } else { EdgeKind::Fallthrough };
+                edges.push(CfgEdge { from, to, kind });
+            }
+        }
+        CfgFunction { name, blocks, edges }
+    }
+
+    /// The block a label starts
+    pub fn block(&self, label : &str) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|b| b.label.as_deref() == Some(label))
+    }
+
+    /// Ids of the blocks control can pass to from `block`
+    pub fn successors(&self, block : usize) -> Vec<usize> {
+        self.edges.iter().filter(|e| e.from == block).map(|e| e.to).collect()
+    }
+}
+
+fn mnemonic(code : &str) -> &str {
+    code.split_whitespace().next().unwrap_or("")
+}
+
+/// Last operand, which is the target of every branch both instruction sets have
+fn target(code : &str) -> &str {
+    let operands = code.trim().split_once(char::is_whitespace).map_or("", |(_, rest)| rest);
+    operands.rsplit(',').next().unwrap_or("").trim()
+}
+
+fn terminator(code : &str, arch : Arch) -> Terminator<'_> {
+    let mnemonic = mnemonic(code);
+    match arch {
+        Arch::X86_64 => match mnemonic {
+            "ret" | "retq" | "retl" | "ud2" | "hlt" => Terminator::Exit,
+            "jmp" | "jmpq" => Terminator::Jump(target(code)),
+            m if m.starts_with('j') || m.starts_with("loop") => Terminator::Conditional(target(code)),
+            _ => Terminator::None,
+        },
+        Arch::AArch64 => match mnemonic {
+            "ret" | "br" | "eret" | "brk" | "udf" => Terminator::Exit,
+            "b" => Terminator::Jump(target(code)),
+            "cbz" | "cbnz" | "tbz" | "tbnz" => Terminator::Conditional(target(code)),
+            m if m.starts_with("b.") => Terminator::Conditional(target(code)),
+            _ => Terminator::None,
+        },
+    }
+}
+
+/// Escapes text for a double-quoted DOT string
+fn escape(text : &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
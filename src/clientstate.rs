@@ -0,0 +1,250 @@
+use base64::{engine, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{link, CompilationFilters, GodboltError, LibraryRef};
+
+// Every type here keeps the keys it does not model in `extra`, so state decoded from a
+// link is written back unchanged. Lists that are missing are written back as empty.
+
+/// Everything the Compiler Explorer UI needs to restore a layout, as stored in links
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct ClientState {
+    #[serde(default)]
+    pub sessions : Vec<Session>,
+    /// Multi-file projects
+    #[serde(default)]
+    pub trees : Vec<Tree>,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+impl ClientState {
+    /// Encodes the state as it appears in `/clientstate/` links
+    pub fn to_base64(&self) -> Result<String, GodboltError> {
+        let str = match serde_json::to_string::<ClientState>(self) {
+            Ok(str) => str,
+            Err(e) => {
+                return Err(GodboltError::Serialize(e));
+            }
+        };
+
+        Ok(engine::general_purpose::STANDARD.encode(str))
+    }
+
+    /// Decodes the base64 of a `/clientstate/` link
+    pub fn from_base64(state : &str) -> Result<ClientState, GodboltError> {
+        link::from_base64(state)
+    }
+
+    /// Decodes a shared link: `/clientstate/<base64>`, a full link with the layout in its
+    /// `#fragment`, raw base64 or JSON. Short `/z/` links must be expanded first.
+    pub fn from_link(link : &str) -> Result<ClientState, GodboltError> {
+        link::decode(link)
+    }
+}
+
+/// An editor and the panes attached to it
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct Session {
+    #[serde(default)]
+    pub id : i32,
+    /// Language id
+    #[serde(default)]
+    pub language : String,
+    #[serde(default)]
+    pub source : String,
+    /// Conformance view comparing compilers on this source; written as `false` when absent
+    #[serde(default, with = "conformance")]
+    pub conformanceview : Option<ConformanceView>,
+    #[serde(default)]
+    pub compilers : Vec<SessionCompiler>,
+    #[serde(default)]
+    pub executors : Vec<Executor>,
+    /// Name of the file the editor holds, when it is part of a tree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename : Option<String>,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// A compiler pane
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct SessionCompiler {
+    /// Pane id, unique within the state
+    #[serde(rename = "_internalid", default, skip_serializing_if = "Option::is_none")]
+    pub internal_id : Option<i64>,
+    /// Compiler id
+    #[serde(default)]
+    pub id : String,
+    /// Flags passed to the compiler
+    #[serde(default)]
+    pub options : String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters : Option<CompilationFilters>,
+    #[serde(default)]
+    pub libs : Vec<SessionLibrary>,
+    /// Extra output panes opened for the compiler (i.e. ast, ir, opt, gccdump)
+    #[serde(default)]
+    pub specialoutputs : Vec<String>,
+    #[serde(default)]
+    pub tools : Vec<SessionTool>,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// Compiler of an executor pane, stored like a compiler pane
+pub type ExecutorCompiler = SessionCompiler;
+
+/// A library as the client state names it
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct SessionLibrary {
+    /// Library id
+    pub name : String,
+    /// Version id
+    pub ver : String,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+impl From<&LibraryRef> for SessionLibrary {
+    fn from(lib : &LibraryRef) -> Self {
+        SessionLibrary {
+            name: lib.id.clone(),
+            ver: lib.version.clone(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl From<&SessionLibrary> for LibraryRef {
+    fn from(lib : &SessionLibrary) -> Self {
+        LibraryRef::new(&lib.name, &lib.ver)
+    }
+}
+
+/// A tool pane attached to a compiler
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct SessionTool {
+    /// Tool id
+    pub id : String,
+    #[serde(default)]
+    pub args : String,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// An executor pane, which compiles and runs the source
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct Executor {
+    #[serde(rename = "compilerVisible", default)]
+    pub compiler_visible : bool,
+    #[serde(rename = "compilerOutputVisible", default)]
+    pub compiler_output_visible : bool,
+    /// Program arguments, space separated
+    #[serde(default)]
+    pub arguments : String,
+    #[serde(rename = "argumentsVisible", default)]
+    pub arguments_visible : bool,
+    #[serde(default)]
+    pub stdin : String,
+    #[serde(rename = "stdinVisible", default)]
+    pub stdin_visible : bool,
+    #[serde(default)]
+    pub compiler : ExecutorCompiler,
+    /// Whether long output lines are wrapped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrap : Option<bool>,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// Runs one source through several compilers to compare which accept it
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct ConformanceView {
+    #[serde(default)]
+    pub libs : Vec<SessionLibrary>,
+    #[serde(default)]
+    pub compilers : Vec<SessionCompiler>,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// A multi-file project. Its files are opened in the sessions with matching ids.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct Tree {
+    #[serde(default)]
+    pub id : i32,
+    #[serde(rename = "cmakeArgs", default)]
+    pub cmake_args : String,
+    #[serde(rename = "customOutputFilename", default)]
+    pub custom_output_filename : String,
+    #[serde(rename = "isCMakeProject", default)]
+    pub is_cmake_project : bool,
+    #[serde(rename = "compilerLanguageId", default)]
+    pub compiler_language_id : String,
+    #[serde(default)]
+    pub files : Vec<TreeFile>,
+    /// Id the next file added in the UI gets
+    #[serde(rename = "newFileId", default)]
+    pub new_file_id : i32,
+    #[serde(default)]
+    pub compilers : Vec<SessionCompiler>,
+    #[serde(default)]
+    pub executors : Vec<Executor>,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// A file of a [`Tree`]
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct TreeFile {
+    #[serde(rename = "fileId", default)]
+    pub file_id : i32,
+    /// Whether the file is compiled (or, for headers, available to include)
+    #[serde(rename = "isIncluded", default)]
+    pub is_included : bool,
+    #[serde(rename = "isOpen", default)]
+    pub is_open : bool,
+    #[serde(rename = "isMainSource", default)]
+    pub is_main_source : bool,
+    #[serde(default)]
+    pub filename : String,
+    #[serde(default)]
+    pub content : String,
+    /// Id of the session editing the file
+    #[serde(rename = "editorId", default)]
+    pub editor_id : i32,
+    #[serde(rename = "langId", default)]
+    pub lang_id : String,
+    /// Keys this crate does not model
+    #[serde(flatten)]
+    pub extra : Map<String, Value>,
+}
+
+/// Compiler Explorer writes a missing conformance view as `false` rather than leaving it out
+mod conformance {
+    use super::*;
+
+    pub fn serialize<S : Serializer>(view : &Option<ConformanceView>, serializer : S) -> Result<S::Ok, S::Error> {
+        match view {
+            Some(view) => view.serialize(serializer),
+            None => serializer.serialize_bool(false),
+        }
+    }
+
+    pub fn deserialize<'de, D : Deserializer<'de>>(deserializer : D) -> Result<Option<ConformanceView>, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Object(map) => serde_json::from_value(Value::Object(map)).map(Some).map_err(serde::de::Error::custom),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use serde::Deserialize;
+
+use crate::{GodboltResponse, StdErrResult, StdOutResult, ValidationIssue};
+
+/// File Compiler Explorer configures CMake projects from
+pub const CMAKELISTS : &str = "CMakeLists.txt";
+
+/// Result of compiling a CMake project with [`Godbolt::send_cmake_request`](crate::Godbolt::send_cmake_request)
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct CMakeResponse {
+    /// Exit code of the last step that ran
+    pub code : i32,
+    /// Configure and build steps, in the order they ran
+    #[serde(rename = "buildsteps", default)]
+    pub build_steps : Vec<BuildStep>,
+    /// Output of compiling the project's output file, absent if the build failed
+    pub result : Option<GodboltResponse>,
+    /// Output of running the built program, when execution was requested
+    #[serde(rename = "execResult")]
+    pub exec_result : Option<ExecutionResult>,
+    #[serde(rename = "didExecute")]
+    pub did_execute : Option<bool>,
+    #[serde(default)]
+    pub stdout : Vec<StdOutResult>,
+    #[serde(default)]
+    pub stderr : Vec<StdErrResult>,
+    /// Number of attempts it took to get this response
+    #[serde(skip)]
+    pub attempts : u32,
+    /// Options the compiler ignored, found while validating the request
+    #[serde(skip)]
+    pub warnings : Vec<ValidationIssue>,
+}
+
+impl CMakeResponse {
+    /// The step with this name (i.e. cmake, build)
+    pub fn step(&self, name : &str) -> Option<&BuildStep> {
+        self.build_steps.iter().find(|s| s.step == name)
+    }
+
+    /// Running `cmake` to configure the project
+    pub fn configure_step(&self) -> Option<&BuildStep> {
+        self.step("cmake")
+    }
+
+    /// Building the configured project
+    pub fn build_step(&self) -> Option<&BuildStep> {
+        self.step("build")
+    }
+
+    /// Whether every step that ran succeeded
+    pub fn succeeded(&self) -> bool {
+        self.code == 0 && self.build_steps.iter().all(|s| s.code == 0)
+    }
+}
+
+/// One command run while building a CMake project
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct BuildStep {
+    /// Step name (i.e. cmake, build)
+    pub step : String,
+    pub code : i32,
+    #[serde(default)]
+    pub stdout : Vec<StdOutResult>,
+    #[serde(default)]
+    pub stderr : Vec<StdErrResult>,
+}
+
+/// Output of running a built program
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct ExecutionResult {
+    /// Exit code of the program
+    pub code : i32,
+    #[serde(rename = "didExecute")]
+    pub did_execute : Option<bool>,
+    #[serde(default)]
+    pub stdout : Vec<StdOutResult>,
+    #[serde(default)]
+    pub stderr : Vec<StdErrResult>,
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{AsmResult, GodboltResponse};
+use crate::asm::{is_local_label, strip_comment};
+
+/// Lines of context around changes in [`AsmDiff::unified`] hunks, as `diff -u` uses
+pub const DIFF_CONTEXT : usize = 3;
+
+/// Differences between the assembly of two compilations, function by function
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AsmDiff {
+    /// Functions of the left side in order, then those only the right side has
+    pub functions : Vec<FunctionDiff>,
+}
+
+/// How a function differs between the two sides
+#[derive(Clone, Copy, Debug, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FunctionStatus {
+    #[default]
+    Unchanged,
+    Changed,
+    /// Only on the right side
+    Added,
+    /// Only on the left side
+    Removed,
+}
+
+/// One normalized line of a function diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// A function present on either side
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionDiff {
+    pub name : String,
+    pub status : FunctionStatus,
+    pub lines : Vec<DiffLine>,
+    pub summary : FunctionSummary,
+}
+
+/// Counts of a [`FunctionDiff`], for tooling
+#[derive(Clone, Debug, Serialize, Default, PartialEq, Eq)]
+pub struct FunctionSummary {
+    pub name : String,
+    pub status : FunctionStatus,
+    /// Instructions on the left side, labels excluded
+    pub left_instructions : usize,
+    pub right_instructions : usize,
+    /// `right_instructions - left_instructions`
+    pub delta : i64,
+    pub added : usize,
+    pub removed : usize,
+    /// Removed instructions directly replaced by added ones; these are not counted
+    /// in `added` or `removed`
+    pub changed : usize,
+}
+
+impl GodboltResponse {
+    /// Compares this response's assembly (the left side) with `other`'s
+    pub fn diff_asm(&self, other : &GodboltResponse) -> AsmDiff {
+        AsmDiff::new(self.asm_lines(), other.asm_lines())
+    }
+}
+
+impl AsmDiff {
+    /// Aligns both listings by function and diffs their normalized instructions.
+    /// Directives, comments and addresses are dropped and local labels (`.L3`, `.LBB0_2`)
+    /// are renumbered in order of appearance, so only real changes are reported.
+    pub fn new(left : &[AsmResult], right : &[AsmResult]) -> AsmDiff {
+        let left = functions(left);
+        let mut right = functions(right);
+
+        let mut diffs = Vec::new();
+        for (name, before) in left {
+            let after = right.iter().position(|(n, _)| *n == name).map(|i| right.remove(i).1);
+            diffs.push(FunctionDiff::new(name, Some(before), after));
+        }
+        for (name, after) in right {
+            diffs.push(FunctionDiff::new(name, None, Some(after)));
+        }
+        AsmDiff { functions: diffs }
+    }
+
+    /// Whether the two sides match once normalized
+    pub fn is_empty(&self) -> bool {
+        self.functions.iter().all(|f| f.status == FunctionStatus::Unchanged)
+    }
+
+    /// A function by name
+    pub fn function(&self, name : &str) -> Option<&FunctionDiff> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    /// Per-function counts of every function on either side
+    pub fn summary(&self) -> Vec<FunctionSummary> {
+        self.functions.iter().map(|f| f.summary.clone()).collect()
+    }
+
+    /// Renders the changed functions as a unified diff, with `context` unchanged lines
+    /// around each change
+    pub fn unified(&self, context : usize) -> String {
+        let mut out = String::new();
+        for function in &self.functions {
+            if function.status == FunctionStatus::Unchanged {
+                continue;
+            }
+            let (from, to) = match function.status {
+                FunctionStatus::Added => ("/dev/null".to_string(), format!("b/{}", function.name)),
+                FunctionStatus::Removed => (format!("a/{}", function.name), "/dev/null".to_string()),
+                _ => (format!("a/{}", function.name), format!("b/{}", function.name)),
+            };
+            out.push_str(&format!("--- {}\n+++ {}\n", from, to));
+            for hunk in hunks(&function.lines, context) {
+                out.push_str(&hunk);
+            }
+        }
+        out
+    }
+}
+
+impl FunctionDiff {
+    fn new(name : String, before : Option<Vec<String>>, after : Option<Vec<String>>) -> FunctionDiff {
+        let status = match (&before, &after) {
+            (Some(_), None) => FunctionStatus::Removed,
+            (None, _) => FunctionStatus::Added,
+            (Some(b), Some(a)) if b == a => FunctionStatus::Unchanged,
+            _ => FunctionStatus::Changed,
+        };
+        let before = before.unwrap_or_default();
+        let after = after.unwrap_or_default();
+        let lines = diff_lines(&before, &after);
+
+        let left_instructions = before.iter().filter(|l| !is_label(l)).count();
+        let right_instructions = after.iter().filter(|l| !is_label(l)).count();
+        let mut summary = FunctionSummary {
+            name: name.clone(),
+            status,
+            left_instructions,
+            right_instructions,
+            delta: right_instructions as i64 - left_instructions as i64,
+            ..Default::default()
+        };
+        // Runs of removals followed by additions are instructions that changed
+        let (mut removed, mut added) = (0, 0);
+        for line in lines.iter().map(Some).chain(std::iter::once(None)) {
+            match line {
+                Some(DiffLine::Removed(l)) if !is_label(l) => removed += 1,
+                Some(DiffLine::Added(l)) if !is_label(l) => added += 1,
+                Some(DiffLine::Removed(_)) | Some(DiffLine::Added(_)) => {}
+                _ => {
+                    let changed = removed.min(added);
+                    summary.changed += changed;
+                    summary.removed += removed - changed;
+                    summary.added += added - changed;
+                    removed = 0;
+                    added = 0;
+                }
+            }
+        }
+        FunctionDiff { name, status, lines, summary }
+    }
+}
+
+/// Splits a listing into functions, each a list of normalized lines. Anything before the
+/// first function is kept under an empty name.
+fn functions(asm : &[AsmResult]) -> Vec<(String, Vec<String>)> {
+    let mut out : Vec<(String, Vec<String>)> = Vec::new();
+    let mut labels = HashMap::new();
+    for line in asm {
+        if let Some(name) = line.label_definition().filter(|n| !is_local_label(n)) {
+            out.push((name.to_string(), Vec::new()));
+            labels.clear();
+            continue;
+        }
+        if let Some(normalized) = normalize(line.text(), &mut labels) {
+            if out.is_empty() {
+                out.push((String::new(), Vec::new()));
+            }
+            out.last_mut().unwrap().1.push(normalized);
+        }
+    }
+    out
+}
+
+/// Normalizes an assembly line for comparison, `None` for lines that carry no code
+fn normalize(text : &str, labels : &mut HashMap<String, String>) -> Option<String> {
+    let code = strip_comment(text).trim();
+    // Disassembly prefixes instructions with their address (`401126:`). Labels are not
+    // indented, so one named like a number (`add:`) is left alone.
+    let code = match code.split_once(':') {
+        Some((address, rest)) if text.starts_with(char::is_whitespace)
+            && !address.is_empty() && address.chars().all(|c| c.is_ascii_hexdigit()) => rest.trim(),
+        _ => code,
+    };
+    if code.is_empty() || (code.starts_with('.') && !code.ends_with(':')) {
+        return None;
+    }
+
+    let chars : Vec<char> = code.chars().chain(std::iter::once(' ')).collect();
+    let mut out = String::with_capacity(code.len());
+    let mut token = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_alphanumeric() || matches!(c, '_' | '.' | '$') {
+            token.push(c);
+            continue;
+        }
+        if !token.is_empty() {
+            // Disassembly names branch targets as `401020 <puts@plt>`
+            let symbol_follows = chars[i..].iter().find(|c| !c.is_whitespace()) == Some(&'<');
+            out.push_str(&normalize_token(&token, symbol_follows, labels));
+            token.clear();
+        }
+        if c.is_whitespace() {
+            if !out.is_empty() && !out.ends_with(' ') {
+                out.push(' ');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out.trim_end().to_string())
+}
+
+/// Renames local labels and replaces addresses. Only `0x` numbers of six or more digits
+/// and numbers followed by a `<symbol>` are addresses; other numbers are immediates.
+fn normalize_token(token : &str, symbol_follows : bool, labels : &mut HashMap<String, String>) -> String {
+    if is_local_label(token) {
+        let next = labels.len();
+        return labels.entry(token.to_string()).or_insert_with(|| format!(".L{}", next)).clone();
+    }
+    let address = match token.strip_prefix("0x") {
+        Some(digits) => digits.len() >= 6 && digits.chars().all(|c| c.is_ascii_hexdigit()),
+        None => symbol_follows && token.chars().all(|c| c.is_ascii_hexdigit()),
+    };
+    if address {
+        return "<addr>".to_string();
+    }
+    token.to_string()
+}
+
+fn is_label(line : &str) -> bool {
+    line.ends_with(':')
+}
+
+/// Longest-common-subsequence diff, removals before additions within a change
+fn diff_lines(before : &[String], after : &[String]) -> Vec<DiffLine> {
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if before[i] == after[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && before[i] == after[j] {
+            out.push(DiffLine::Same(before[i].clone()));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(DiffLine::Removed(before[i].clone()));
+            i += 1;
+        } else {
+            out.push(DiffLine::Added(after[j].clone()));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Groups changes that are within `2 * context` lines of each other into `@@` hunks
+fn hunks(lines : &[DiffLine], context : usize) -> Vec<String> {
+    let changes : Vec<usize> = lines.iter().enumerate()
+        .filter(|(_, l)| !matches!(l, DiffLine::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut ranges : Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges.into_iter().map(|(start, end)| {
+        // One-based line numbers on each side where the hunk starts
+        let old_start = lines[..start].iter().filter(|l| !matches!(l, DiffLine::Added(_))).count() + 1;
+        let new_start = lines[..start].iter().filter(|l| !matches!(l, DiffLine::Removed(_))).count() + 1;
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|l| !matches!(l, DiffLine::Added(_))).count();
+        let new_len = hunk.iter().filter(|l| !matches!(l, DiffLine::Removed(_))).count();
+
+        let mut out = format!("@@ -{} +{} @@\n", range(old_start, old_len), range(new_start, new_len));
+        for line in hunk {
+            match line {
+                DiffLine::Same(l) => out.push_str(&format!(" {}\n", l)),
+                DiffLine::Removed(l) => out.push_str(&format!("-{}\n", l)),
+                DiffLine::Added(l) => out.push_str(&format!("+{}\n", l)),
+            }
+        }
+        out
+    }).collect()
+}
+
+/// `start,len` as `diff -u` writes it; an empty side starts at the line before
+fn range(start : usize, len : usize) -> String {
+    if len == 0 {
+        format!("{},0", start - 1)
+    } else {
+        format!("{},{}", start, len)
+    }
+}
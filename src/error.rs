@@ -0,0 +1,148 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+use crate::ValidationIssue;
+
+/// Number of characters kept on each side of a deserialization failure
+const SNIPPET_RADIUS : usize = 80;
+
+/// Errors returned by every fallible call in this crate
+#[derive(Debug)]
+pub enum GodboltError {
+    /// The request could not be sent, or the response could not be read
+    Transport(reqwest::Error),
+    /// The server answered with a non-success status
+    Http {
+        /// HTTP status code
+        status : u16,
+        /// Response body, as text
+        body : String,
+    },
+    /// The response did not match the expected schema
+    Deserialize {
+        /// Underlying parse error
+        error : serde_json::Error,
+        /// Raw JSON surrounding the position the parser gave up at
+        snippet : String,
+    },
+    /// The server answered with HTTP 429
+    RateLimited {
+        /// How long the server asked us to wait, if it said
+        retry_after : Option<Duration>,
+    },
+    /// The input was rejected before any request was made
+    Validation(Vec<ValidationIssue>),
+    /// The client could not be configured (bad base URL, header or proxy)
+    Config(String),
+    /// A request body or client state could not be serialized
+    Serialize(serde_json::Error),
+    /// A shared link or client state could not be decoded
+    InvalidLink(String),
+    /// A snapshot could not be read or written
+    Io(std::io::Error),
+    /// Every attempt allowed by the [`RetryPolicy`](crate::RetryPolicy) failed
+    RetriesExhausted {
+        /// Number of attempts made
+        attempts : u32,
+        /// Error from the final attempt
+        last : Box<GodboltError>,
+    },
+}
+
+impl GodboltError {
+    /// Builds a [`GodboltError::Deserialize`] with the part of `text` the parser choked on
+    pub(crate) fn deserialize(error : serde_json::Error, text : &str) -> GodboltError {
+        let snippet = snippet_at(text, error.line(), error.column());
+        GodboltError::Deserialize { error, snippet }
+    }
+
+    /// HTTP status code, if the server answered at all
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            GodboltError::Http { status, .. } => Some(*status),
+            GodboltError::RateLimited { .. } => Some(429),
+            GodboltError::Transport(e) => e.status().map(|s| s.as_u16()),
+            GodboltError::RetriesExhausted { last, .. } => last.status(),
+            _ => None,
+        }
+    }
+
+    /// Number of attempts made before giving up
+    pub fn attempts(&self) -> u32 {
+        match self {
+            GodboltError::RetriesExhausted { attempts, .. } => *attempts,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for GodboltError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GodboltError::Transport(e) => write!(f, "transport error: {}", e),
+            GodboltError::Http { status, body } => write!(f, "HTTP {}: {}", status, body),
+            GodboltError::Deserialize { error, snippet } => write!(f, "unexpected response ({}) near `{}`", error, snippet),
+            GodboltError::RateLimited { retry_after: Some(d) } => write!(f, "rate limited, retry after {}s", d.as_secs()),
+            GodboltError::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            GodboltError::Validation(issues) => {
+                let issues : Vec<String> = issues.iter().map(ValidationIssue::to_string).collect();
+                write!(f, "invalid request: {}", issues.join("; "))
+            }
+            GodboltError::Config(msg) => write!(f, "invalid configuration: {}", msg),
+            GodboltError::InvalidLink(msg) => write!(f, "invalid link: {}", msg),
+            GodboltError::Serialize(e) => write!(f, "could not serialize request: {}", e),
+            GodboltError::Io(e) => write!(f, "I/O error: {}", e),
+            GodboltError::RetriesExhausted { attempts, last } => write!(f, "gave up after {} attempts: {}", attempts, last),
+        }
+    }
+}
+
+impl std::error::Error for GodboltError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GodboltError::Transport(e) => Some(e),
+            GodboltError::Deserialize { error, .. } => Some(error),
+            GodboltError::Serialize(e) => Some(e),
+            GodboltError::Io(e) => Some(e),
+            GodboltError::RetriesExhausted { last, .. } => Some(last.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GodboltError {
+    fn from(e : std::io::Error) -> Self {
+        GodboltError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for GodboltError {
+    fn from(e : reqwest::Error) -> Self {
+        GodboltError::Transport(e)
+    }
+}
+
+/// Reads a `Retry-After` header given either in seconds or as an HTTP date
+pub(crate) fn retry_after(headers : &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Cuts out the text around a 1-based line/column position
+fn snippet_at(text : &str, line : usize, column : usize) -> String {
+    let offset : usize = text.split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>() + column.saturating_sub(1);
+
+    let chars : Vec<char> = text.chars().collect();
+    let at = text.char_indices().take_while(|(i, _)| *i < offset).count();
+    let start = at.saturating_sub(SNIPPET_RADIUS);
+    let end = (at + SNIPPET_RADIUS).min(chars.len());
+    chars[start..end].iter().collect()
+}
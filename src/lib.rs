@@ -1,12 +1,56 @@
-use std::error::Error;
 use serde::*;
-use reqwest::header::{ACCEPT, USER_AGENT};
-use std::fmt;
-use base64::{engine, Engine};
-
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+mod asm;
+mod builder;
+mod cfg;
+mod clientstate;
+mod cmake;
+mod diff;
+mod error;
+mod libspec;
+mod limiter;
+mod link;
+mod lzstring;
+mod mca;
+mod query;
+mod render;
+mod retry;
+mod rison;
+mod snapshot;
+mod suggest;
 mod tests;
-
-#[derive(Clone, Debug, Deserialize)]
+mod tools;
+mod validate;
+mod version;
+
+pub use asm::{AsmLabel, AsmResult, AsmSource, LabelRange};
+pub use builder::{GodboltBuilder, DEFAULT_USER_AGENT};
+pub use cfg::{Arch, BasicBlock, Cfg, CfgEdge, CfgFunction, EdgeKind};
+pub use clientstate::{ClientState, ConformanceView, Executor, ExecutorCompiler, Session, SessionCompiler, SessionLibrary, SessionTool, Tree, TreeFile};
+pub use cmake::{BuildStep, CMakeResponse, ExecutionResult, CMAKELISTS};
+pub use diff::{AsmDiff, DiffLine, FunctionDiff, FunctionStatus, FunctionSummary, DIFF_CONTEXT};
+pub use error::GodboltError;
+pub use libspec::{LibraryError, LibrarySpec};
+pub use mca::{InstructionPressure, McaInstruction, McaReport};
+pub use query::{CompilerQuery, QueryPage};
+pub use render::RenderOptions;
+pub use retry::RetryPolicy;
+pub use snapshot::GodboltSnapshot;
+pub use suggest::Suggestion;
+pub use tools::{ToolRequest, ToolResult};
+pub use validate::{ValidationIssue, ValidationReport};
+pub use version::{CompilerVersion, Version};
+pub use reqwest::Proxy;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Compiler {
     /// Unique compiler id
     pub id : String,
@@ -16,9 +60,79 @@ pub struct Compiler {
     pub lang : String,
     /// List of aliases to the compiler
     pub alias : Vec<String>,
+    /// Target instruction set (i.e. amd64, aarch64, riscv64)
+    #[serde(rename = "instructionSet", default, skip_serializing_if = "Option::is_none")]
+    pub instruction_set : Option<String>,
+    /// Compiler implementation (i.e. gcc, clang, win32-vc)
+    #[serde(rename = "compilerType", default, skip_serializing_if = "Option::is_none")]
+    pub compiler_type : Option<String>,
+    /// Version as shown in the UI (i.e. 13.2, trunk)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semver : Option<String>,
+    /// First line of the compiler's own version output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version : Option<String>,
+    /// Id of the group the compiler is listed under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group : Option<String>,
+    /// Display name of the group the compiler is listed under
+    #[serde(rename = "groupName", default, skip_serializing_if = "Option::is_none")]
+    pub group_name : Option<String>,
+    /// Whether compiled programs can be run
+    #[serde(rename = "supportsExecute", default, skip_serializing_if = "Option::is_none")]
+    pub supports_execute : Option<bool>,
+    /// Whether output can be disassembled from a linked binary
+    #[serde(rename = "supportsBinary", default, skip_serializing_if = "Option::is_none")]
+    pub supports_binary : Option<bool>,
+    /// Whether output can be disassembled from an object file
+    #[serde(rename = "supportsBinaryObject", default, skip_serializing_if = "Option::is_none")]
+    pub supports_binary_object : Option<bool>,
+    /// Whether Intel assembly syntax is available
+    #[serde(rename = "supportsIntel", default, skip_serializing_if = "Option::is_none")]
+    pub supports_intel : Option<bool>,
+    /// Whether symbols can be demangled
+    #[serde(rename = "supportsDemangle", default, skip_serializing_if = "Option::is_none")]
+    pub supports_demangle : Option<bool>,
+    /// Tools that can run alongside the compiler, keyed by tool id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools : BTreeMap<String, CompilerTool>,
+    /// Ids of the libraries the compiler can use. Empty means no restriction.
+    #[serde(rename = "libsArr", default, skip_serializing_if = "Vec::is_empty")]
+    pub libs : Vec<String>,
+    /// Filters that have no effect with this compiler (i.e. intel, binary)
+    #[serde(rename = "disabledFilters", default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_filters : Vec<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl Compiler {
+    /// Family, version, trunk flag and architecture, derived from metadata and the display name
+    pub fn version_info(&self) -> CompilerVersion {
+        version::parse(self)
+    }
+
+    /// A tool that can run alongside the compiler, by id
+    pub fn tool(&self, id : &str) -> Option<&CompilerTool> {
+        self.tools.get(id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct CompilerTool {
+    /// Unique tool id
+    #[serde(default)]
+    pub id : String,
+    /// Tool display name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name : Option<String>,
+    /// Whether the tool runs on the compiler's output or on its own (postcompilation, independent)
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub tool_type : Option<String>,
+    /// Whether the tool accepts stdin
+    #[serde(rename = "allowStdin", default, skip_serializing_if = "Option::is_none")]
+    pub allow_stdin : Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Language {
     /// Unique language id
     pub id : String,
@@ -33,7 +147,7 @@ pub struct Language {
     pub default_compiler : String
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Library {
     /// Unique identifier of library
     pub id : String,
@@ -45,7 +159,7 @@ pub struct Library {
     pub versions : Vec<LibraryVersion>
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LibraryVersion {
     /// Version of the library
     pub version : String,
@@ -67,7 +181,7 @@ pub struct LibraryVersion {
     pub id : String
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Format {
     /// Path to executable
     pub exe : String,
@@ -88,16 +202,16 @@ pub struct GodboltCacheEntry {
     pub language : Language,
     /// List of compilers for the language
     pub compilers : Vec<Compiler>,
-}
-
-#[derive(Clone, Debug, Deserialize, Default)]
-pub struct AsmResult {
-    pub text : Option<String>
+    /// Libraries for the language, empty until loaded with [`Godbolt::load_libraries`]
+    pub libraries : Vec<Library>,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
 pub struct StdOutResult {
-    pub text : String
+    pub text : String,
+    /// Source location the line refers to, for diagnostics
+    #[serde(default)]
+    pub tag : Option<TagResult>,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -108,9 +222,17 @@ pub struct StdErrResult {
 
 #[derive(Clone, Debug, Deserialize, Default)]
 pub struct TagResult {
+    #[serde(default)]
     pub line : i32,
+    #[serde(default)]
     pub column : i32,
-    pub text : String
+    pub text : String,
+    /// Severity as Monaco numbers it (8 error, 4 warning, 2 info)
+    #[serde(default)]
+    pub severity : Option<i32>,
+    /// File the diagnostic is in, when it is not the main source
+    #[serde(default)]
+    pub file : Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -122,7 +244,9 @@ pub struct BuildResult {
     pub input_filename : Option<String>,
     #[serde(rename = "compilationOptions")]
     pub compilation_options : Option<Vec<String>>,
-    pub tools : Option<Vec<String>>,
+    /// Output of the tools that ran on the build
+    #[serde(default)]
+    pub tools : Vec<ToolResult>,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -141,13 +265,33 @@ pub struct GodboltResponse {
     pub build_result: Option<BuildResult>,
     #[serde(rename = "execTime")]
     pub execution_time: Option<i32>,
+    #[serde(default)]
     pub stdout : Vec<StdOutResult>,
+    #[serde(default)]
     pub stderr : Vec<StdErrResult>,
     #[serde(rename = "asmSize")]
     pub asm_size : Option<i32>,
     pub asm : Option<Vec<AsmResult>>,
+    /// Labels defined in [`GodboltResponse::asm`], mapped to the one-based line defining them
+    #[serde(rename = "labelDefinitions", default)]
+    pub label_definitions : BTreeMap<String, usize>,
     #[serde(rename = "ppOutput")]
     pub pp_output : Option<PpResult>,
+    /// Output of the tools requested in [`RequestOptions::tools`]
+    #[serde(default)]
+    pub tools : Vec<ToolResult>,
+    /// Number of attempts it took to get this response
+    #[serde(skip)]
+    pub attempts : u32,
+    /// Options the compiler ignored, found while validating the request
+    #[serde(skip)]
+    pub warnings : Vec<ValidationIssue>,
+}
+
+/// Answer of `/api/shortener`
+#[derive(Clone, Debug, Deserialize)]
+struct ShortenerResponse {
+    url : String,
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -165,6 +309,9 @@ pub struct CompilerOptions {
     pub executor_request : bool,
     #[serde(rename = "producePp", skip_serializing_if = "Option::is_none")]
     pub produce_pp : Option<ProducePp>,
+    /// Arguments to pass to `cmake` when configuring a CMake project
+    #[serde(rename = "cmakeArgs", skip_serializing_if = "Option::is_none")]
+    pub cmake_args : Option<String>,
 }
 
 #[derive(Clone, Serialize, Debug, Default)]
@@ -191,7 +338,49 @@ pub struct RequestOptions {
     #[serde(rename = "executeParameters")]
     pub execute_parameters : ExecuteParameters,
     /// Filters
-    pub filters : CompilationFilters
+    pub filters : CompilationFilters,
+    /// Libraries to make available to the compiler
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub libraries : Vec<LibraryRef>,
+    /// Tools to run alongside the compiler
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools : Vec<ToolRequest>,
+}
+
+/// A library and version to compile with, by id (i.e. fmt 101)
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+pub struct LibraryRef {
+    /// Unique library id, [`Library::id`]
+    pub id : String,
+    /// Unique version id, [`LibraryVersion::id`]
+    pub version : String,
+}
+
+impl LibraryRef {
+    pub fn new(id : &str, version : &str) -> Self {
+        LibraryRef {
+            id: id.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+/// A named file compiled alongside the main source (i.e. a header or another translation unit)
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Path relative to the main source, as it is #included (i.e. util/math.h)
+    pub filename : String,
+    /// File contents
+    pub contents : String,
+}
+
+impl SourceFile {
+    pub fn new(filename : &str, contents : &str) -> Self {
+        SourceFile {
+            filename: filename.to_string(),
+            contents: contents.to_string(),
+        }
+    }
 }
 
 /// Struct containing information needed to submit a compilation request
@@ -203,6 +392,191 @@ pub struct CompilationRequest {
     compiler : String,
     /// List of compilation options
     options : RequestOptions,
+    /// Additional files, written next to the main source
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    files : Vec<SourceFile>,
+    /// Name of the main source in client state trees
+    #[serde(skip)]
+    main_filename : Option<String>,
+    /// The compiler the request was built for, to validate against
+    #[serde(skip)]
+    target : Compiler,
+    /// Whether this is a CMake project, sent to the `/cmake` endpoint
+    #[serde(skip)]
+    cmake : bool,
+}
+
+impl CompilationRequest {
+    pub fn new(c : &Compiler, source : &str, options : RequestOptions) -> Self {
+        CompilationRequest {
+            source: source.to_string(),
+            compiler: c.id.clone(),
+            options,
+            files: vec![],
+            main_filename: None,
+            target: c.clone(),
+            cmake: false,
+        }
+    }
+
+    /// A CMake project made of `files`, one of which must be [`CMAKELISTS`].
+    /// `cmake_args` are passed to `cmake` when configuring it.
+    pub fn cmake(c : &Compiler, files : &[SourceFile], cmake_args : &str, mut options : RequestOptions) -> Self {
+        options.compiler_options.cmake_args = Some(cmake_args.to_string());
+        let mut req = CompilationRequest::new(c, "", options);
+        req.cmake = true;
+        for file in files {
+            if file.filename == CMAKELISTS {
+                req.source = file.contents.clone();
+                req.main_filename = Some(CMAKELISTS.to_string());
+            } else {
+                req = req.with_file(&file.filename, &file.contents);
+            }
+        }
+        req
+    }
+
+    /// Adds a file next to the main source. A file with the same name is replaced.
+    pub fn with_file(mut self, filename : &str, contents : &str) -> Self {
+        self.files.retain(|f| f.filename != filename);
+        self.files.push(SourceFile::new(filename, contents));
+        self
+    }
+
+    /// Names the main source in client state trees, `example` plus the language's usual extension by default
+    pub fn with_main_filename(mut self, filename : &str) -> Self {
+        self.main_filename = Some(filename.to_string());
+        self
+    }
+
+    /// Source code to compile
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Compiler identifier
+    pub fn compiler(&self) -> &str {
+        &self.compiler
+    }
+
+    /// List of compilation options
+    pub fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    /// Additional files, in the order they were added
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Whether this is a CMake project
+    pub fn is_cmake(&self) -> bool {
+        self.cmake
+    }
+
+    /// Name of the main source
+    pub fn main_filename(&self) -> String {
+        match &self.main_filename {
+            Some(name) => name.clone(),
+            None => format!("example{}", default_extension(&self.target.lang)),
+        }
+    }
+
+    /// Client state that opens this request in the Compiler Explorer UI. With additional
+    /// files, the sources are opened as a tree that the compiler and executor are attached to.
+    pub fn client_state(&self) -> ClientState {
+        let libs : Vec<SessionLibrary> = self.options.libraries.iter().map(SessionLibrary::from).collect();
+        let lang = self.target.lang.clone();
+        let compiler = SessionCompiler {
+            id: self.compiler.clone(),
+            options: self.options.user_arguments.clone(),
+            filters: Some(self.options.filters.clone()),
+            libs,
+            tools: self.options.tools.iter().map(|t| SessionTool { id: t.id.clone(), args: t.args.clone(), ..Default::default() }).collect(),
+            ..Default::default()
+        };
+        let params = &self.options.execute_parameters;
+        let executor = Executor {
+            arguments: link::join_arguments(&params.args),
+            arguments_visible: !params.args.is_empty(),
+            stdin: params.stdin.clone(),
+            stdin_visible: !params.stdin.is_empty(),
+            compiler: compiler.clone(),
+            ..Default::default()
+        };
+
+        if self.files.is_empty() && !self.cmake {
+            return ClientState {
+                sessions: vec![
+                    Session {
+                        id: 0,
+                        language: lang,
+                        source: self.source.clone(),
+                        compilers: vec![compiler],
+                        executors: vec![executor],
+                        ..Default::default()
+                    }
+                ],
+                ..Default::default()
+            };
+        }
+
+        let main = self.main_filename();
+        let all = std::iter::once((main.as_str(), self.source.as_str()))
+            .chain(self.files.iter().map(|f| (f.filename.as_str(), f.contents.as_str())));
+
+        let mut state = ClientState::default();
+        let mut tree = Tree {
+            id: 1,
+            cmake_args: self.options.compiler_options.cmake_args.clone().unwrap_or_default(),
+            is_cmake_project: self.cmake,
+            compiler_language_id: lang.clone(),
+            compilers: vec![compiler],
+            executors: vec![executor],
+            ..Default::default()
+        };
+        for (i, (filename, contents)) in all.enumerate() {
+            let id = i as i32 + 1;
+            state.sessions.push(Session {
+                id,
+                language: lang.clone(),
+                source: contents.to_string(),
+                filename: Some(filename.to_string()),
+                ..Default::default()
+            });
+            tree.files.push(TreeFile {
+                file_id: id,
+                is_included: true,
+                is_open: true,
+                is_main_source: i == 0,
+                filename: filename.to_string(),
+                content: contents.to_string(),
+                editor_id: id,
+                lang_id: lang.clone(),
+                ..Default::default()
+            });
+        }
+        tree.new_file_id = tree.files.len() as i32 + 1;
+        state.trees.push(tree);
+        state
+    }
+}
+
+/// Extension Compiler Explorer gives new sources of a language
+fn default_extension(lang : &str) -> &'static str {
+    match lang {
+        "c++" => ".cpp",
+        "c" => ".c",
+        "rust" => ".rs",
+        "go" => ".go",
+        "d" => ".d",
+        "zig" => ".zig",
+        "swift" => ".swift",
+        "cuda" => ".cu",
+        "fortran" => ".f90",
+        "python" => ".py",
+        _ => "",
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Default)]
@@ -210,14 +584,18 @@ pub struct FormatterRequest {
     source : String,
     #[serde(skip_serializing_if = "Option::is_none")]
     base: Option<String>,
-    useSpaces: bool,
-    tabWidth: i32
+    #[serde(rename = "useSpaces")]
+    use_spaces: bool,
+    #[serde(rename = "tabWidth")]
+    tab_width: i32
 }
 
-#[derive(Clone, Debug, Serialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub struct CompilationFilters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub binary : Option<bool>,
+    #[serde(rename = "binaryObject", skip_serializing_if = "Option::is_none")]
+    pub binary_object : Option<bool>,
     #[serde(rename = "commentOnly", skip_serializing_if = "Option::is_none")]
     pub comment_only : Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -233,82 +611,130 @@ pub struct CompilationFilters {
     #[serde(rename = "libraryCode", skip_serializing_if = "Option::is_none")]
     pub library_code : Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub trim : Option<bool>
+    pub trim : Option<bool>,
+    #[serde(rename = "debugCalls", skip_serializing_if = "Option::is_none")]
+    pub debug_calls : Option<bool>,
+    /// Filters this crate does not model, kept so client state round-trips
+    #[serde(flatten)]
+    pub extra : serde_json::Map<String, serde_json::Value>,
 }
 
+/// Base URL of the public Compiler Explorer instance
+pub const DEFAULT_BASE_URL : &str = "https://godbolt.org";
+
 /// A struct with calls to all of Godbolt Compiler Explorer's endpoints
 pub struct Godbolt {
     /// Internal cache of godbolt languages and their associated compilers
     pub cache : Vec<GodboltCacheEntry>,
     /// Cache of all formatting tools
     pub formats : Vec<Format>,
+    /// Base URL of the Compiler Explorer instance, without a trailing slash
+    base_url : String,
+    /// HTTP client shared by every request
+    client : reqwest::Client,
+    /// How failed requests are retried
+    retry : RetryPolicy,
+    /// Requests-per-second budget shared by every call
+    limiter : Option<Arc<limiter::RateLimiter>>,
+    /// Bounds the number of requests in flight at once
+    in_flight : Option<Arc<Semaphore>>,
+    /// Background refresh of a stale snapshot, see [`Godbolt::from_snapshot`]
+    refresh : Option<JoinHandle<Result<GodboltSnapshot, GodboltError>>>,
+    /// Lowercased compiler ids and aliases, as (cache entry, compiler) positions
+    compiler_index : HashMap<String, (usize, usize)>,
+    /// Lowercased language ids, names and file extensions, as cache entry positions
+    language_index : HashMap<String, usize>,
 }
 
-#[derive(Debug)]
-pub struct GodboltError {
-    details: String
+/// Joins an API path onto a base URL, tolerating a trailing slash on the base
+fn endpoint(base_url : &str, path : &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), path.trim_start_matches('/'))
 }
 
-#[derive(Clone, Debug, Serialize, Default)]
-pub struct ClientState {
-    pub sessions : Vec<Session>
-}
-
-#[derive(Clone, Debug, Serialize, Default)]
-pub struct Session {
-    pub id : i32,
-    pub language : String,
-    pub source : String,
-    pub compilers : Vec<SessionCompiler>,
-    pub executors : Vec<Executor>
-}
+impl Godbolt {
+    /// Connects to the public instance at [`DEFAULT_BASE_URL`]
+    pub async fn new() -> Result<Self, GodboltError> {
+        GodboltBuilder::new().build().await
+    }
 
-#[derive(Clone, Debug, Serialize, Default)]
-pub struct SessionCompiler {
-    pub id : String,
-    pub options : String,
-}
+    /// Connects to the Compiler Explorer instance at `base_url`.
+    /// Path prefixes are kept, so `https://ce.corp/explorer/` is a valid base.
+    pub async fn with_base_url(base_url : &str) -> Result<Self, GodboltError> {
+        GodboltBuilder::new().base_url(base_url).build().await
+    }
 
-#[derive(Clone, Debug, Serialize, Default)]
-pub struct Executor {
-    pub arguments : String,
-    pub compiler : ExecutorCompiler,
-    pub stdin : String,
-}
+    /// Starts configuring a client
+    pub fn builder() -> GodboltBuilder {
+        GodboltBuilder::new()
+    }
 
-#[derive(Clone, Debug, Serialize, Default)]
-pub struct ExecutorCompiler {
-    pub id : String,
-    pub libs : Vec<String>,
-    pub options : String
-}
+    /// Builds an instance from a snapshot written by [`Godbolt::save_snapshot`], without
+    /// touching the network. A snapshot older than `ttl` is still used, but a fresh
+    /// one is fetched and written back to `path` in the background; see
+    /// [`Godbolt::finish_refresh`]. A missing snapshot is fetched and written first.
+    pub async fn from_snapshot<P : AsRef<Path>>(path : P, ttl : Duration) -> Result<Self, GodboltError> {
+        GodboltBuilder::new().build_from_snapshot(path, ttl).await
+    }
 
-impl GodboltError {
-    fn new(msg: &str) -> GodboltError {
-        GodboltError{details: msg.to_string()}
+    /// Fetches formats, languages and compilers and rebuilds the cache
+    async fn load_cache(&mut self) -> Result<(), GodboltError> {
+        let snapshot = self.fetch_snapshot().await?;
+        self.apply_snapshot(snapshot);
+        Ok(())
     }
-}
-impl fmt::Display for GodboltError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.details)
+
+    /// Fetches everything the cache holds. Libraries are fetched again for every
+    /// language that currently has them loaded.
+    pub async fn fetch_snapshot(&self) -> Result<GodboltSnapshot, GodboltError> {
+        let formats = self.get_formats().await?;
+        let languages = self.get_languages().await?;
+        let compilers = self.get_compilers().await?;
+
+        let mut libraries = BTreeMap::new();
+        for entry in self.cache.iter().filter(|e| !e.libraries.is_empty()) {
+            let id = &entry.language.id;
+            libraries.insert(id.clone(), self.get_libraries_for(id).await?);
+        }
+
+        Ok(GodboltSnapshot {
+            created_at: snapshot::now(),
+            base_url: self.base_url.clone(),
+            languages,
+            compilers,
+            formats,
+            libraries,
+        })
     }
-}
-impl std::error::Error for GodboltError {
-}
 
-impl Godbolt {
-    pub async fn new() -> Result<Self, Box<dyn Error>> {
-        let formats = Godbolt::get_formats().await?;
+    /// Current contents of the cache
+    pub fn snapshot(&self) -> GodboltSnapshot {
+        let mut libraries = BTreeMap::new();
+        for entry in self.cache.iter().filter(|e| !e.libraries.is_empty()) {
+            libraries.insert(entry.language.id.clone(), entry.libraries.clone());
+        }
 
-        let mut instance = Godbolt {
-            cache: Vec::new(),
-            formats
-        };
+        GodboltSnapshot {
+            created_at: snapshot::now(),
+            base_url: self.base_url.clone(),
+            languages: self.cache.iter().map(|e| e.language.clone()).collect(),
+            compilers: self.cache.iter().flat_map(|e| e.compilers.iter().cloned()).collect(),
+            formats: self.formats.clone(),
+            libraries,
+        }
+    }
 
-        let langs = Godbolt::get_languages().await?;
-        let compilers = Godbolt::get_compilers().await?;
+    /// Writes the current cache to `path` as JSON
+    pub fn save_snapshot<P : AsRef<Path>>(&self, path : P) -> Result<(), GodboltError> {
+        self.snapshot().save(path)
+    }
 
-        for lang in langs {
+    /// Replaces the cache with the contents of a snapshot
+    pub fn apply_snapshot(&mut self, snapshot : GodboltSnapshot) {
+        let GodboltSnapshot { languages, compilers, formats, mut libraries, .. } = snapshot;
+        self.formats = formats;
+
+        self.cache.clear();
+        for lang in languages {
             let mut relevant = Vec::new();
             for compiler in &compilers {
                 if lang.id == compiler.lang {
@@ -317,16 +743,234 @@ impl Godbolt {
             }
 
             let cache = GodboltCacheEntry {
+                libraries: libraries.remove(&lang.id).unwrap_or_default(),
                 language: lang,
                 compilers: relevant,
             };
-            instance.cache.push(cache);
+            self.cache.push(cache);
         }
+        self.reindex();
+    }
 
-        Ok(instance)
+    /// Rebuilds the lookup indexes from `cache`. Lookups stay correct when `cache` is modified
+    /// directly, but fall back to scanning it until this is called.
+    pub fn reindex(&mut self) {
+        self.compiler_index.clear();
+        self.language_index.clear();
+
+        // Ids are inserted first so an alias or extension never shadows an id
+        for (e, entry) in self.cache.iter().enumerate() {
+            self.language_index.insert(entry.language.id.to_lowercase(), e);
+            for (c, compiler) in entry.compilers.iter().enumerate() {
+                self.compiler_index.insert(compiler.id.to_lowercase(), (e, c));
+            }
+        }
+        for (e, entry) in self.cache.iter().enumerate() {
+            self.language_index.entry(entry.language.name.to_lowercase()).or_insert(e);
+            for (c, compiler) in entry.compilers.iter().enumerate() {
+                for alias in &compiler.alias {
+                    self.compiler_index.entry(alias.to_lowercase()).or_insert((e, c));
+                }
+            }
+        }
+        for (e, entry) in self.cache.iter().enumerate() {
+            for ext in &entry.language.extensions {
+                let ext = ext.to_lowercase();
+                self.language_index.entry(ext.trim_start_matches('.').to_string()).or_insert(e);
+                self.language_index.entry(ext).or_insert(e);
+            }
+        }
+    }
+
+    /// Fetches the libraries of a language into its cache entry
+    pub async fn load_libraries(&mut self, language_id : &str) -> Result<&[Library], GodboltError> {
+        let index = match self.cache.iter().position(|e| e.language.id.eq_ignore_ascii_case(language_id)) {
+            Some(index) => index,
+            None => return Err(GodboltError::Validation(vec![ValidationIssue::UnknownLanguage(language_id.to_string())])),
+        };
+
+        let libraries = self.get_libraries_for(&self.cache[index].language.id).await?;
+        self.cache[index].libraries = libraries;
+        Ok(&self.cache[index].libraries)
+    }
+
+    /// Cached libraries of a language; empty if none were loaded
+    pub fn cached_libraries(&self, language_id : &str) -> &[Library] {
+        match self.cache.iter().find(|e| e.language.id == language_id) {
+            Some(entry) => &entry.libraries,
+            None => &[],
+        }
+    }
+
+    /// Tools that can run alongside a cached compiler, by compiler id or alias; empty if it is unknown
+    pub fn tools_for(&self, compiler : &str) -> Vec<&CompilerTool> {
+        match self.find_compiler_by_id(compiler) {
+            Some(c) => c.tools.values().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Resolves library specs such as `fmt@trunk`, `boost` or `range-v3@0.12` against the
+    /// cached libraries of a language, adding their dependencies transitively.
+    /// Every problem found is reported, not just the first.
+    pub fn resolve_libraries(&self, language : &str, specs : &[&str]) -> Result<Vec<LibraryRef>, Vec<LibraryError>> {
+        let libraries = match self.find_language_by_id(language) {
+            Some(lang) => self.cached_libraries(&lang.id),
+            None => &[],
+        };
+        if libraries.is_empty() {
+            return Err(vec![LibraryError::NotLoaded(language.to_string())]);
+        }
+
+        let mut parsed = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs {
+            match LibrarySpec::parse(spec) {
+                Ok(spec) => parsed.push(spec),
+                Err(e) => errors.push(e),
+            }
+        }
+        match libspec::resolve(libraries, &parsed) {
+            Ok(refs) if errors.is_empty() => Ok(refs),
+            Ok(_) => Err(errors),
+            Err(more) => {
+                errors.extend(more);
+                Err(errors)
+            }
+        }
+    }
+
+    /// Starts fetching a fresh snapshot in the background, writing it to `path` once done
+    fn spawn_refresh(&mut self, path : &Path) {
+        let mut fetcher = self.detached();
+        fetcher.cache = self.cache.iter()
+            .filter(|e| !e.libraries.is_empty())
+            .map(|e| GodboltCacheEntry { language: e.language.clone(), compilers: vec![], libraries: e.libraries.clone() })
+            .collect();
+        let path = path.to_path_buf();
+
+        self.refresh = Some(tokio::spawn(async move {
+            let snapshot = fetcher.fetch_snapshot().await?;
+            snapshot.save(&path)?;
+            Ok(snapshot)
+        }));
+    }
+
+    /// Whether a background refresh started by [`Godbolt::from_snapshot`] is pending
+    pub fn is_refreshing(&self) -> bool {
+        self.refresh.is_some()
+    }
+
+    /// Waits for a pending background refresh and swaps its data into the cache.
+    /// Returns `false` if no refresh was pending.
+    pub async fn finish_refresh(&mut self) -> Result<bool, GodboltError> {
+        let handle = match self.refresh.take() {
+            Some(handle) => handle,
+            None => return Ok(false),
+        };
+
+        match handle.await {
+            Ok(Ok(snapshot)) => {
+                self.apply_snapshot(snapshot);
+                Ok(true)
+            }
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(GodboltError::Io(std::io::Error::other(e))),
+        }
+    }
+
+    /// An instance sharing this one's client and limits, with an empty cache
+    fn detached(&self) -> Godbolt {
+        Godbolt {
+            cache: Vec::new(),
+            formats: Vec::new(),
+            base_url: self.base_url.clone(),
+            client: self.client.clone(),
+            retry: self.retry.clone(),
+            limiter: self.limiter.clone(),
+            in_flight: self.in_flight.clone(),
+            refresh: None,
+            compiler_index: HashMap::new(),
+            language_index: HashMap::new(),
+        }
+    }
+
+    /// Sends a request and decodes its JSON response, mapping every failure onto [`GodboltError`]
+    async fn execute<T : DeserializeOwned>(&self, request : reqwest::RequestBuilder) -> Result<T, GodboltError> {
+        self.execute_counted(request).await.map(|(res, _)| res)
+    }
+
+    /// Like [`Godbolt::execute`], retrying per the configured [`RetryPolicy`].
+    /// Returns the number of attempts alongside the result.
+    async fn execute_counted<T : DeserializeOwned>(&self, request : reqwest::RequestBuilder) -> Result<(T, u32), GodboltError> {
+        let request = request.build()?;
+        let mut attempt = 1;
+        loop {
+            // Bodies built from JSON are always cloneable, only streams are not
+            let (this, next) = match request.try_clone() {
+                Some(copy) if attempt < self.retry.max_attempts => (copy, true),
+                Some(copy) => (copy, false),
+                None => return self.attempt(request).await.map(|res| (res, attempt)),
+            };
+
+            let err = match self.attempt(this).await {
+                Ok(res) => return Ok((res, attempt)),
+                Err(e) => e,
+            };
+            if !next || !RetryPolicy::is_retryable(&err) {
+                return Err(match attempt {
+                    1 => err,
+                    _ => GodboltError::RetriesExhausted { attempts: attempt, last: Box::new(err) },
+                });
+            }
+
+            tokio::time::sleep(self.retry.delay(attempt, &err)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Makes a single attempt at a request, waiting for the rate limiter and
+    /// a free in-flight slot first
+    async fn attempt<T : DeserializeOwned>(&self, request : reqwest::Request) -> Result<T, GodboltError> {
+        // Held until the body is read, the semaphore is never closed
+        let _permit = match &self.in_flight {
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        };
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+
+        let response = self.client.execute(request).await?;
+        let status = response.status();
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(GodboltError::RateLimited {
+                retry_after: error::retry_after(response.headers()),
+            });
+        }
+
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(GodboltError::Http {
+                status: status.as_u16(),
+                body: text,
+            });
+        }
+
+        match serde_json::from_str::<T>(&text) {
+            Ok(res) => Ok(res),
+            Err(e) => Err(GodboltError::deserialize(e, &text))
+        }
+    }
+
+    /// Base URL of the Compiler Explorer instance this was built against
+    pub fn base_url(&self) -> &str {
+        &self.base_url
     }
 
-    /// Determines if the input compiler is a valid one
+    /// Resolves a compiler id or alias, or a language id, name or extension to its
+    /// default compiler
     pub fn resolve(&self, target : &str) -> Option<Compiler> {
         if let Some(comp) = self.find_compiler_by_id(target) {
             Some(comp.clone())
@@ -337,182 +981,241 @@ impl Godbolt {
         }
     }
 
-    pub fn find_compiler_by_id(&self, compiler_id : &str) -> Option<&Compiler> {
-        for entry in &self.cache {
-            for compiler in &entry.compilers {
-                if compiler.id.to_lowercase() == compiler_id.to_lowercase() {
-                    return Some(&compiler);
+    /// Like [`Godbolt::resolve`], but when nothing matches returns up to `limit` of the
+    /// closest compilers instead. When `language` names a known language (by id, name
+    /// or extension) only its compilers are suggested.
+    pub fn resolve_with_suggestions(&self, target : &str, language : Option<&str>, limit : usize) -> Result<Compiler, Vec<Suggestion>> {
+        match self.resolve(target) {
+            Some(compiler) => Ok(compiler),
+            None => Err(self.suggest(target, language, limit)),
+        }
+    }
+
+    /// Up to `limit` compilers whose id, aliases or name most closely match `target`, best first
+    pub fn suggest(&self, target : &str, language : Option<&str>, limit : usize) -> Vec<Suggestion> {
+        let scope = language.and_then(|l| self.find_language_by_id(l)).map(|l| l.id.as_str());
+        let compilers = self.cache.iter()
+            .filter(|e| scope.is_none_or(|id| e.language.id == id))
+            .flat_map(|e| e.compilers.iter());
+        suggest::rank(target, compilers, limit)
+    }
+
+    /// Cached compilers matching `query`, sorted and paginated
+    pub fn query(&self, query : &CompilerQuery) -> QueryPage<'_> {
+        query.run(self)
+    }
+
+    /// Newest released compiler of `family` (i.e. "gcc", "clang", "msvc") for a language.
+    /// Among equal versions, the one targeting the language's default architecture wins.
+    pub fn latest(&self, family : &str, language : &str) -> Option<&Compiler> {
+        self.newest_of(family, language, false)
+    }
+
+    /// Trunk, nightly or snapshot build of `family` for a language
+    pub fn trunk(&self, family : &str, language : &str) -> Option<&Compiler> {
+        self.newest_of(family, language, true)
+    }
+
+    fn newest_of(&self, family : &str, language : &str, trunk : bool) -> Option<&Compiler> {
+        let family = version::normalize_family(family);
+        let lang = self.find_language_by_id(language)?;
+        let entry = self.cache.iter().find(|e| e.language.id == lang.id)?;
+        let default_arch = self.find_compiler_by_id(&lang.default_compiler)
+            .and_then(|c| c.version_info().arch);
+
+        entry.compilers.iter()
+            .map(|c| (c, c.version_info()))
+            .filter(|(_, v)| v.family == family && v.trunk == trunk && (trunk || v.version.is_some()))
+            .max_by(|(a, va), (b, vb)| va.version.cmp(&vb.version)
+                .then_with(|| (va.arch == default_arch).cmp(&(vb.arch == default_arch)))
+                .then_with(|| b.id.len().cmp(&a.id.len()))
+                .then_with(|| b.id.cmp(&a.id)))
+            .map(|(c, _)| c)
+    }
+
+    /// Released compilers of `family`, in every language, whose version falls in `range`,
+    /// oldest first. i.e. `compilers_in_range("clang", Version::new(15, 0, 0)..)`
+    pub fn compilers_in_range<R : RangeBounds<Version>>(&self, family : &str, range : R) -> Vec<&Compiler> {
+        let family = version::normalize_family(family);
+        let mut found : Vec<(&Compiler, Version)> = self.cache.iter()
+            .flat_map(|e| e.compilers.iter())
+            .filter_map(|c| {
+                let info = c.version_info();
+                match info.version {
+                    Some(v) if info.family == family && !info.trunk && range.contains(&v) => Some((c, v)),
+                    _ => None,
                 }
-            }
+            })
+            .collect();
+        found.sort_by(|(a, va), (b, vb)| va.cmp(vb).then_with(|| a.id.cmp(&b.id)));
+        found.into_iter().map(|(c, _)| c).collect()
+    }
+
+    /// Finds a compiler by id or alias, ignoring case
+    pub fn find_compiler_by_id(&self, compiler_id : &str) -> Option<&Compiler> {
+        let key = compiler_id.trim().to_lowercase();
+        // `cache` may have changed since the last `reindex`, so hits are checked against it
+        let indexed = self.compiler_index.get(&key)
+            .and_then(|&(e, c)| self.cache.get(e)?.compilers.get(c))
+            .filter(|c| c.id.to_lowercase() == key || c.alias.iter().any(|a| a.to_lowercase() == key));
+        if indexed.is_some() {
+            return indexed;
         }
-        None
+        let compilers = || self.cache.iter().flat_map(|e| e.compilers.iter());
+        compilers().find(|c| c.id.to_lowercase() == key)
+            .or_else(|| compilers().find(|c| c.alias.iter().any(|a| a.to_lowercase() == key)))
     }
 
-    /// Determines if the input language is a valid one
+    /// Finds a language by id, display name or file extension (with or without the dot), ignoring case
     pub fn find_language_by_id(&self, language_id : &str) -> Option<&Language> {
-        for entry in &self.cache {
-            if entry.language.id.to_lowercase() == language_id.to_lowercase() {
-                return Some(&entry.language);
-            }
+        let key = language_id.trim().to_lowercase();
+        let has_extension = |l : &Language| l.extensions.iter()
+            .map(|x| x.to_lowercase())
+            .any(|x| x == key || x.trim_start_matches('.') == key);
+        // `cache` may have changed since the last `reindex`, so hits are checked against it
+        let indexed = self.language_index.get(&key)
+            .and_then(|&e| self.cache.get(e))
+            .map(|e| &e.language)
+            .filter(|l| l.id.to_lowercase() == key || l.name.to_lowercase() == key || has_extension(l));
+        if indexed.is_some() {
+            return indexed;
         }
-        None
+        let languages = || self.cache.iter().map(|e| &e.language);
+        languages().find(|l| l.id.to_lowercase() == key)
+            .or_else(|| languages().find(|l| l.name.to_lowercase() == key))
+            .or_else(|| languages().find(|l| has_extension(l)))
     }
 
-    pub async fn send_request(c : &Compiler, source : &str, options : RequestOptions, user_agent : &str) -> Result<GodboltResponse, GodboltError>{
-        let req = CompilationRequest {
-            compiler: c.id.clone(),
-            source: String::from(source),
-            options : options
-        };
+    /// Checks a request against the cached capabilities of its compiler, and its
+    /// libraries against the cached library list if loaded, without any network
+    pub fn validate(&self, request : &CompilationRequest) -> ValidationReport {
+        match self.find_compiler_by_id(&request.compiler) {
+            Some(compiler) => validate::check(compiler, self.cached_libraries(&compiler.lang), request),
+            None => ValidationReport {
+                errors: vec![ValidationIssue::UnknownCompiler(request.compiler.clone())],
+                warnings: vec![],
+            },
+        }
+    }
 
-//        let send = match serde_json::to_string(&req) {
-//            Ok(res) => res,
-//            Err(e) => return Err(GodboltError::new(&format!("{}", e)))
-//        };
-//        println!("send: {}", send);
-
-        let client = reqwest::Client::new();
-        let endpoint = format!("https://godbolt.org/api/compiler/{}/compile", c.id);
-
-        let result = match client.post(&endpoint)
-            .json(&req)
-            .header(USER_AGENT, user_agent)
-            .header(ACCEPT, "application/json")
-            .send().await {
-            Ok(res) => res,
-            Err(e) => return Err(GodboltError::new(&format!("{}", e)))
-        };
+    /// Compiles `source`. The request is first checked against `c`'s capabilities and
+    /// rejected with [`GodboltError::Validation`] if it cannot succeed; options that
+    /// would be ignored are reported in [`GodboltResponse::warnings`].
+    pub async fn send_request(&self, c : &Compiler, source : &str, options : RequestOptions) -> Result<GodboltResponse, GodboltError>{
+        self.send_compilation(&CompilationRequest::new(c, source, options)).await
+    }
 
-        let text = match result.text().await {
-            Ok(res) => res,
-            Err(e) => return Err(GodboltError::new(&format!("{}", e)))
-        };
+    /// Like [`Godbolt::send_request`], for a request built up front (i.e. with [`CompilationRequest::with_file`])
+    pub async fn send_compilation(&self, req : &CompilationRequest) -> Result<GodboltResponse, GodboltError> {
+        let (mut res, attempts, warnings) = self.post_compilation::<GodboltResponse>(req, "compile").await?;
+        res.attempts = attempts;
+        res.warnings = warnings;
+        Ok(res)
+    }
 
-//        println!("{}", text);
-        let res = match serde_json::from_str::<GodboltResponse>(&text) {
-            Ok(res) => res,
-            Err(e) => return Err(GodboltError::new(&format!("{}", e)))
-        };
+    /// Configures and builds a CMake project made of `files`, which must include [`CMAKELISTS`].
+    /// Validated like [`Godbolt::send_request`].
+    pub async fn send_cmake_request(&self, c : &Compiler, files : &[SourceFile], cmake_args : &str, options : RequestOptions) -> Result<CMakeResponse, GodboltError> {
+        self.send_cmake_compilation(&CompilationRequest::cmake(c, files, cmake_args, options)).await
+    }
 
+    /// Like [`Godbolt::send_cmake_request`], for a request built with [`CompilationRequest::cmake`]
+    pub async fn send_cmake_compilation(&self, req : &CompilationRequest) -> Result<CMakeResponse, GodboltError> {
+        let (mut res, attempts, warnings) = self.post_compilation::<CMakeResponse>(req, "cmake").await?;
+        res.attempts = attempts;
+        res.warnings = warnings;
         Ok(res)
     }
 
-    pub fn get_base64(c : &Compiler, source : &str, options : RequestOptions) -> Result<String, GodboltError> {
-        let cstate = ClientState {
-            sessions: vec![
-                Session {
-                    id: 0,
-                    language: c.lang.clone(),
-                    source: source.to_string(),
-                    compilers: vec![
-                        SessionCompiler {
-                            id: c.id.clone(),
-                            options: options.user_arguments.clone(),
-                        }
-                    ],
-                    executors: vec![
-                        Executor {
-                            arguments: options.execute_parameters.args.join(" "),
-                            compiler: ExecutorCompiler {
-                                id: c.id.clone(),
-                                libs: vec![],
-                                options: options.user_arguments,
-                            },
-                            stdin: options.execute_parameters.stdin,
-                        }
-                    ],
-                }
-            ]
-        };
+    /// Validates `req` and posts it to `api/compiler/{id}/{action}`, returning the
+    /// response, the attempts it took and the validation warnings. The cached compiler
+    /// is checked against when there is one, as callers may pass a bare `Compiler`.
+    async fn post_compilation<T : DeserializeOwned>(&self, req : &CompilationRequest, action : &str) -> Result<(T, u32, Vec<ValidationIssue>), GodboltError> {
+        let c = self.find_compiler_by_id(&req.compiler).unwrap_or(&req.target);
+        let report = validate::check(c, self.cached_libraries(&c.lang), req);
+        if !report.is_ok() {
+            return Err(GodboltError::Validation(report.errors));
+        }
 
-        let str = match serde_json::to_string::<ClientState>(&cstate) {
-            Ok(str) => str,
-            Err(e) => {
-                return Err(GodboltError::new(&e.to_string()));
-            }
-        };
+        let endpoint = endpoint(&self.base_url, &format!("api/compiler/{}/{}", req.compiler, action));
+        let (res, attempts) = self.execute_counted::<T>(self.client.post(endpoint).json(req)).await?;
+        Ok((res, attempts, report.warnings))
+    }
 
-        Ok(engine::general_purpose::STANDARD.encode(str))
+    pub fn get_base64(c : &Compiler, source : &str, options : RequestOptions) -> Result<String, GodboltError> {
+        CompilationRequest::new(c, source, options).client_state().to_base64()
     }
 
-    /// Retrieves a vector of languages
-    pub async fn get_languages() -> Result<Vec<Language>, Box<dyn Error>>{
-        static LANGUAGE_ENDPOINT : &str = "https://godbolt.org/api/languages?fields=id,name,extensions,monaco,defaultCompiler";
+    /// Builds a request for every compiler, executor and conformance view compiler in `state`.
+    /// Compilers that are not cached are sent as is, without capability checks.
+    pub fn requests_from_state(&self, state : &ClientState) -> Vec<CompilationRequest> {
+        link::requests(self, state)
+    }
 
-        let client = reqwest::Client::new();
-        let res = client
-            .get(LANGUAGE_ENDPOINT)
-            .header(USER_AGENT, "godbolt-rust-crate")
-            .header(ACCEPT, "application/json")
-            .send()
-            .await?;
+    /// Decodes a shared link (see [`ClientState::from_link`]) into its state and the requests to re-run it
+    pub fn decode_link(&self, link : &str) -> Result<(ClientState, Vec<CompilationRequest>), GodboltError> {
+        let state = ClientState::from_link(link)?;
+        let requests = self.requests_from_state(&state);
+        Ok((state, requests))
+    }
 
-        let results : Vec<Language>  = res.json::<Vec<Language>>().await?;
-        Ok(results)
+    /// Stores `state` with the instance's shortener and returns the `/z/<id>` link to it
+    pub async fn shorten(&self, state : &ClientState) -> Result<String, GodboltError> {
+        let res : ShortenerResponse = self.execute(self.client
+            .post(endpoint(&self.base_url, "api/shortener"))
+            .json(state)).await?;
+        Ok(res.url)
     }
 
-    /// Retrieves a vector of compilers
-    pub async fn get_compilers() -> Result<Vec<Compiler>, Box<dyn Error>>{
-        static LANGUAGE_ENDPOINT : &str = "https://godbolt.org/api/compilers?fields=id,name,lang,alias";
+    /// Fetches the state behind a short link, given its id or the whole `/z/<id>` link
+    pub async fn expand_short_link(&self, link : &str) -> Result<ClientState, GodboltError> {
+        let id = link.trim().trim_end_matches('/');
+        let id = match id.rsplit_once("/z/") {
+            Some((_, id)) => id,
+            None => id,
+        };
+        if id.is_empty() || id.contains(['/', '?', '#']) {
+            return Err(GodboltError::InvalidLink(format!("not a short link: {}", link)));
+        }
+        self.execute(self.client.get(endpoint(&self.base_url, &format!("api/shortlinkinfo/{}", urlencoding::encode(id))))).await
+    }
 
-        let client = reqwest::Client::new();
-        let res = client
-            .get(LANGUAGE_ENDPOINT)
-            .header(USER_AGENT, "godbolt-rust-crate")
-            .header(ACCEPT, "application/json")
-            .send()
-            .await?;
+    /// Builds a link that opens the given source and options on this instance
+    pub fn get_link(&self, c : &Compiler, source : &str, options : RequestOptions) -> Result<String, GodboltError> {
+        self.get_compilation_link(&CompilationRequest::new(c, source, options))
+    }
 
-        let results : Vec<Compiler>  = res.json::<Vec<Compiler>>().await?;
-        Ok(results)
+    /// Builds a link that opens the request, with all of its files, on this instance
+    pub fn get_compilation_link(&self, req : &CompilationRequest) -> Result<String, GodboltError> {
+        let state = req.client_state().to_base64()?;
+        Ok(endpoint(&self.base_url, &format!("clientstate/{}", urlencoding::encode(&state))))
     }
 
-    /// Retrieves a vector of compilers for a given language identifier
-    pub async fn get_compilers_for(language_id : &str) -> Result<Vec<Compiler>, Box<dyn Error>> {
-        let endpoint = format!("https://godbolt.org/api/compilers/{}?fields=id,name,lang,alias", language_id);
+    /// Retrieves a vector of languages
+    pub async fn get_languages(&self) -> Result<Vec<Language>, GodboltError>{
+        self.execute(self.client.get(endpoint(&self.base_url, "api/languages?fields=id,name,extensions,monaco,defaultCompiler"))).await
+    }
 
-        let client = reqwest::Client::new();
-        let res = client
-            .get(&endpoint)
-            .header(USER_AGENT, "godbolt-rust-crate")
-            .header(ACCEPT, "application/json")
-            .send()
-            .await?;
+    /// Retrieves a vector of compilers, with all of their metadata
+    pub async fn get_compilers(&self) -> Result<Vec<Compiler>, GodboltError>{
+        self.execute(self.client.get(endpoint(&self.base_url, "api/compilers?fields=all"))).await
+    }
 
-        let results : Vec<Compiler>  = res.json::<Vec<Compiler>>().await?;
-        Ok(results)
+    /// Retrieves a vector of compilers for a given language identifier
+    pub async fn get_compilers_for(&self, language_id : &str) -> Result<Vec<Compiler>, GodboltError> {
+        self.execute(self.client.get(endpoint(&self.base_url, &format!("api/compilers/{}?fields=all", language_id)))).await
     }
 
     /// Retrieves a vector of libraries for a given language identifier
-    pub async fn get_libraries_for(language_id : &str) -> Result<Vec<Library>, Box<dyn Error>> {
-        let endpoint = format!("https://godbolt.org/api/libraries/{}", language_id);
-
-        let client = reqwest::Client::new();
-        let res = client
-            .get(&endpoint)
-            .header(USER_AGENT, "godbolt-rust-crate")
-            .header(ACCEPT, "application/json")
-            .send()
-            .await?;
-
-        let results : Vec<Library>  = res.json::<Vec<Library>>().await?;
-        Ok(results)
+    pub async fn get_libraries_for(&self, language_id : &str) -> Result<Vec<Library>, GodboltError> {
+        self.execute(self.client.get(endpoint(&self.base_url, &format!("api/libraries/{}", language_id)))).await
     }
 
-    pub async fn get_formats() -> Result<Vec<Format>, Box<dyn Error>> {
-        let client = reqwest::Client::new();
-        let res = client
-            .get("https://godbolt.org/api/formats")
-            .header(USER_AGENT, "godbolt-rust-crate")
-            .header(ACCEPT, "application/json")
-            .send()
-            .await?;
-
-        let results : Vec<Format>  = res.json::<Vec<Format>>().await?;
-        Ok(results)
+    pub async fn get_formats(&self) -> Result<Vec<Format>, GodboltError> {
+        self.execute(self.client.get(endpoint(&self.base_url, "api/formats"))).await
     }
 
-    pub async fn format_code(fmt : &str, style : &str, source : &str, useSpaces : bool, tabWidth : i32) -> Result<FormatResult, Box<dyn Error>> {
+    pub async fn format_code(&self, fmt : &str, style : &str, source : &str, use_spaces : bool, tab_width : i32) -> Result<FormatResult, GodboltError> {
         let mut base = Option::None;
         if !style.is_empty() {
             base = Some(String::from(style));
@@ -520,20 +1223,12 @@ impl Godbolt {
         let formatter_request = FormatterRequest {
             source: String::from(source),
             base,
-            useSpaces,
-            tabWidth
+            use_spaces,
+            tab_width
         };
 
-        let client = reqwest::Client::new();
-        let res = client
-            .post(format!("https://godbolt.org/api/format/{}", fmt))
-            .header(USER_AGENT, "godbolt-rust-crate")
-            .header(ACCEPT, "application/json")
-            .json(&formatter_request)
-            .send()
-            .await?;
-
-        let result = res.json::<FormatResult>().await?;
-        Ok(result)
+        self.execute(self.client
+            .post(endpoint(&self.base_url, &format!("api/format/{}", fmt)))
+            .json(&formatter_request)).await
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Library, LibraryRef, LibraryVersion, Version};
+
+/// A library as users type it: `fmt`, `fmt@trunk`, `range-v3@0.12`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LibrarySpec {
+    /// Library id or display name
+    pub name : String,
+    /// Version id, version or alias; the latest release when absent
+    pub version : Option<String>,
+}
+
+impl LibrarySpec {
+    pub fn parse(spec : &str) -> Result<Self, LibraryError> {
+        let spec = spec.trim();
+        let (name, version) = match spec.split_once('@') {
+            Some((name, version)) => (name.trim(), Some(version.trim())),
+            None => (spec, None),
+        };
+
+        let valid = |s : &str| !s.is_empty() && !s.contains(char::is_whitespace) && !s.contains('@');
+        if !valid(name) || !version.is_none_or(valid) {
+            return Err(LibraryError::InvalidSpec(spec.to_string()));
+        }
+        Ok(LibrarySpec {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        })
+    }
+}
+
+impl FromStr for LibrarySpec {
+    type Err = LibraryError;
+
+    fn from_str(s : &str) -> Result<Self, Self::Err> {
+        LibrarySpec::parse(s)
+    }
+}
+
+impl fmt::Display for LibrarySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Why a set of [`LibrarySpec`]s could not be resolved
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LibraryError {
+    /// The spec is not of the form `name` or `name@version`
+    InvalidSpec(String),
+    /// No libraries are cached for the language
+    NotLoaded(String),
+    /// No library has this id or name
+    UnknownLibrary(String),
+    /// The library exists, but not in this version
+    UnknownVersion {
+        /// Library id
+        library : String,
+        /// Requested version
+        version : String,
+    },
+    /// Two different versions of one library were asked for, directly or through dependencies
+    Conflict {
+        /// Library id
+        library : String,
+        /// Version id picked first
+        first : String,
+        /// Version id asked for later
+        second : String,
+    },
+}
+
+impl fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LibraryError::InvalidSpec(spec) => write!(f, "`{}` is not a library spec, expected name or name@version", spec),
+            LibraryError::NotLoaded(lang) => write!(f, "no libraries loaded for `{}`", lang),
+            LibraryError::UnknownLibrary(name) => write!(f, "unknown library `{}`", name),
+            LibraryError::UnknownVersion { library, version } => write!(f, "`{}` has no version `{}`", library, version),
+            LibraryError::Conflict { library, first, second } => write!(f, "`{}` is required in both version `{}` and `{}`", library, first, second),
+        }
+    }
+}
+
+impl std::error::Error for LibraryError {
+}
+
+fn find_library<'a>(libraries : &'a [Library], name : &str) -> Option<&'a Library> {
+    libraries.iter().find(|l| l.id.eq_ignore_ascii_case(name))
+        .or_else(|| libraries.iter().find(|l| l.name.eq_ignore_ascii_case(name)))
+}
+
+/// Finds a version by id, version string or alias, then by dotted prefix ("10.1" finds 10.1.0).
+/// Without a version, picks the newest release, or trunk if there are no releases.
+fn find_version<'a>(library : &'a Library, version : Option<&str>) -> Option<&'a LibraryVersion> {
+    let parsed = |v : &LibraryVersion| Version::parse(&v.version);
+    let newest = |candidates : Vec<&'a LibraryVersion>| candidates.into_iter()
+        .filter(|v| parsed(v).is_some())
+        .max_by_key(|v| parsed(v));
+
+    let version = match version {
+        Some(version) => version,
+        None => {
+            return newest(library.versions.iter().collect())
+                .or_else(|| library.versions.iter().find(|v| v.version == "trunk" || v.id == "trunk"))
+                .or_else(|| library.versions.last());
+        }
+    };
+
+    library.versions.iter()
+        .find(|v| v.id == version || v.version == version || v.alias.iter().any(|a| a == version))
+        .or_else(|| {
+            let prefix = format!("{}.", version);
+            newest(library.versions.iter().filter(|v| v.version.starts_with(&prefix)).collect())
+        })
+}
+
+/// Resolves `specs` against a language's libraries and adds their dependencies,
+/// transitively. Explicit specs come first, in order, followed by dependencies.
+pub(crate) fn resolve(libraries : &[Library], specs : &[LibrarySpec]) -> Result<Vec<LibraryRef>, Vec<LibraryError>> {
+    let mut picked : Vec<LibraryRef> = Vec::new();
+    let mut by_id : HashMap<String, usize> = HashMap::new();
+    let mut errors = Vec::new();
+
+    let mut queue : VecDeque<LibrarySpec> = specs.iter().cloned().collect();
+    while let Some(spec) = queue.pop_front() {
+        let library = match find_library(libraries, &spec.name) {
+            Some(library) => library,
+            None => {
+                errors.push(LibraryError::UnknownLibrary(spec.name.clone()));
+                continue;
+            }
+        };
+
+        // Without a version, whichever version was already picked will do
+        if spec.version.is_none() && by_id.contains_key(&library.id) {
+            continue;
+        }
+
+        let version = match find_version(library, spec.version.as_deref()) {
+            Some(version) => version,
+            None => {
+                errors.push(LibraryError::UnknownVersion {
+                    library: library.id.clone(),
+                    version: spec.version.clone().unwrap_or_default(),
+                });
+                continue;
+            }
+        };
+
+        if let Some(&index) = by_id.get(&library.id) {
+            if picked[index].version != version.id {
+                errors.push(LibraryError::Conflict {
+                    library: library.id.clone(),
+                    first: picked[index].version.clone(),
+                    second: version.id.clone(),
+                });
+            }
+            continue;
+        }
+
+        by_id.insert(library.id.clone(), picked.len());
+        picked.push(LibraryRef::new(&library.id, &version.id));
+        for dep in &version.dependencies {
+            match LibrarySpec::parse(dep) {
+                Ok(dep) => queue.push_back(dep),
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(picked)
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket limiting how many requests are started per second.
+/// Callers that find the bucket empty wait for the next token instead of failing.
+pub(crate) struct RateLimiter {
+    /// Tokens added per second
+    rate : f64,
+    /// Maximum number of tokens the bucket holds
+    burst : f64,
+    bucket : Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens : f64,
+    updated : Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_second : f64, burst : u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            rate: per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a token is available and takes it
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+                bucket.updated = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                // Rates small enough to overflow `Duration` wait for as long as it holds
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate).unwrap_or(Duration::MAX)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
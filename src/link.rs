@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+
+use base64::{engine, Engine};
+use serde_json::{Map, Value};
+
+use crate::{lzstring, rison};
+use crate::{ClientState, CompilationFilters, CompilationRequest, Compiler, ConformanceView, Executor, Godbolt, GodboltError};
+use crate::{LibraryRef, RequestOptions, Session, SessionCompiler, SessionLibrary, SessionTool, SourceFile, ToolRequest, Tree};
+
+/// Keys GoldenLayout shortens to their base 36 index when minifying a layout
+const LAYOUT_KEYS : &[&str] = &[
+    "settings", "hasHeaders", "constrainDragToContainer", "selectionEnabled", "dimensions", "borderWidth",
+    "minItemHeight", "minItemWidth", "headerHeight", "dragProxyWidth", "dragProxyHeight", "labels", "close",
+    "maximise", "minimise", "popout", "content", "componentName", "componentState", "id", "width", "type",
+    "height", "isClosable", "title", "popoutWholeStack", "openPopouts", "parentId", "activeItemIndex",
+    "reorderEnabled", "borderGrabWidth",
+];
+
+/// Values GoldenLayout shortens the same way
+const LAYOUT_VALUES : &[&str] = &[
+    "true", "false", "row", "column", "stack", "component", "close", "maximise", "minimise", "open in new window",
+];
+
+/// Panes that show an extra output of a compiler, stored in [`SessionCompiler::specialoutputs`]
+const SPECIAL_OUTPUTS : &[&str] = &[
+    "ast", "ir", "opt", "cfg", "gccdump", "pp", "device", "llvmOptPipeline", "rustmir", "rusthir",
+    "rustmacroexp", "haskellCore", "haskellStg", "haskellCmm", "gnatdebug", "gnatdebugtree", "stackusage",
+];
+
+/// Decodes any of the forms Compiler Explorer shares state in: a `/clientstate/<base64>`
+/// link, a link with the layout in its `#fragment` (compressed or not), raw base64 or JSON.
+pub(crate) fn decode(link : &str) -> Result<ClientState, GodboltError> {
+    let link = link.trim();
+    let invalid = |why : &str| GodboltError::InvalidLink(format!("{}: {}", why, shorten(link)));
+
+    if let Some((_, state)) = link.split_once("/clientstate/") {
+        let state = state.split(['#', '?']).next().unwrap_or("");
+        let state = urlencoding::decode(state).map_err(|_| invalid("state is not valid UTF-8"))?;
+        return from_base64(&state);
+    }
+    // Before looking for a fragment, as sources are full of `#include`
+    if link.starts_with('{') {
+        return serde_json::from_str(link).map_err(|e| GodboltError::deserialize(e, link));
+    }
+    if let Some((_, fragment)) = link.split_once('#') {
+        return from_fragment(fragment);
+    }
+    if link.contains("/z/") {
+        return Err(invalid("short links must be expanded first, see Godbolt::expand_short_link"));
+    }
+    from_base64(link)
+}
+
+/// Decodes the base64 JSON of `/clientstate/` links
+pub(crate) fn from_base64(state : &str) -> Result<ClientState, GodboltError> {
+    let bytes = engine::general_purpose::STANDARD.decode(state.trim())
+        .or_else(|_| engine::general_purpose::URL_SAFE.decode(state.trim()))
+        .map_err(|e| GodboltError::InvalidLink(format!("not base64 ({}): {}", e, shorten(state))))?;
+    let text = String::from_utf8_lossy(&bytes);
+    serde_json::from_str(&text).map_err(|e| GodboltError::deserialize(e, &text))
+}
+
+/// Decodes the GoldenLayout configuration Compiler Explorer keeps in the URL fragment
+fn from_fragment(fragment : &str) -> Result<ClientState, GodboltError> {
+    let invalid = |why : String| GodboltError::InvalidLink(format!("{}: {}", why, shorten(fragment)));
+
+    let mut layout = match unrisonify(fragment) {
+        Ok(layout) => layout,
+        Err(e) => match url_decode(fragment).ok().and_then(|json| serde_json::from_str(&json).ok()) {
+            Some(json) => json,
+            None => return Err(invalid(e)),
+        },
+    };
+    if let Some(compressed) = layout.get("z").and_then(Value::as_str) {
+        let data = lzstring::decompress_from_base64(compressed)
+            .filter(|data| !data.is_empty())
+            .ok_or_else(|| invalid("corrupted compressed state".to_string()))?;
+        layout = unrisonify(&data).map_err(invalid)?;
+    }
+
+    let layout = unminify(layout);
+    Ok(from_layout(&layout))
+}
+
+fn url_decode(s : &str) -> Result<String, String> {
+    urlencoding::decode(&s.replace('+', "%20"))
+        .map(|s| s.into_owned())
+        .map_err(|e| e.to_string())
+}
+
+fn unrisonify(s : &str) -> Result<Value, String> {
+    rison::decode_object(&url_decode(s)?)
+}
+
+/// Reverses GoldenLayout's minification: one character keys and values are indexes into
+/// [`LAYOUT_KEYS`] and [`LAYOUT_VALUES`], other one character strings are escaped as `___x`
+fn unminify(value : Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(map.into_iter()
+            .map(|(k, v)| (unminify_str(&k, LAYOUT_KEYS), unminify(v)))
+            .collect()),
+        Value::Array(items) => Value::Array(items.into_iter().map(unminify).collect()),
+        Value::String(s) => match unminify_str(&s, LAYOUT_VALUES).as_str() {
+            "true" if s.chars().count() == 1 => Value::Bool(true),
+            "false" if s.chars().count() == 1 => Value::Bool(false),
+            other => Value::String(other.to_string()),
+        },
+        other => other,
+    }
+}
+
+fn unminify_str(s : &str, dictionary : &[&str]) -> String {
+    if let Some(escaped) = s.strip_prefix("___") {
+        return escaped.to_string();
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => match c.to_digit(36).and_then(|i| dictionary.get(i as usize)) {
+            Some(full) => full.to_string(),
+            None => s.to_string(),
+        },
+        _ => s.to_string(),
+    }
+}
+
+/// Every `componentName`/`componentState` pair in the layout, depth first
+fn components(layout : &Value, out : &mut Vec<(String, Map<String, Value>)>) {
+    match layout {
+        Value::Object(map) => {
+            if let (Some(Value::String(name)), Some(Value::Object(state))) = (map.get("componentName"), map.get("componentState")) {
+                out.push((name.clone(), state.clone()));
+            }
+            if let Some(content) = map.get("content") {
+                components(content, out);
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| components(item, out)),
+        _ => {}
+    }
+}
+
+fn int(state : &Map<String, Value>, key : &str) -> Option<i64> {
+    state.get(key).and_then(Value::as_i64)
+}
+
+fn string(state : &Map<String, Value>, key : &str) -> String {
+    match state.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(" "),
+        _ => String::new(),
+    }
+}
+
+fn flag(state : &Map<String, Value>, key : &str) -> bool {
+    state.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn libs(state : &Map<String, Value>) -> Vec<SessionLibrary> {
+    state.get("libs").cloned().and_then(|libs| serde_json::from_value(libs).ok()).unwrap_or_default()
+}
+
+fn compiler_of(state : &Map<String, Value>) -> SessionCompiler {
+    SessionCompiler {
+        internal_id: int(state, "id"),
+        id: string(state, "compiler"),
+        options: string(state, "options"),
+        filters: state.get("filters").cloned().and_then(|f| serde_json::from_value::<CompilationFilters>(f).ok()),
+        libs: libs(state),
+        ..Default::default()
+    }
+}
+
+/// Where a pane's source comes from: an editor or a tree
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Owner {
+    Editor(i64),
+    Tree(i64),
+}
+
+fn owner(state : &Map<String, Value>, editor_keys : &[&str], tree_keys : &[&str]) -> Option<Owner> {
+    tree_keys.iter().find_map(|k| int(state, k)).map(Owner::Tree)
+        .or_else(|| editor_keys.iter().find_map(|k| int(state, k)).map(Owner::Editor))
+}
+
+/// Rebuilds client state from a GoldenLayout configuration, like Compiler Explorer's
+/// `ClientStateNormalizer`
+fn from_layout(layout : &Value) -> ClientState {
+    let mut found = Vec::new();
+    components(layout, &mut found);
+
+    let mut state = ClientState::default();
+    let mut sessions : HashMap<i64, usize> = HashMap::new();
+    let mut trees : HashMap<i64, usize> = HashMap::new();
+
+    for (name, component) in &found {
+        match name.as_str() {
+            "codeEditor" => {
+                let id = int(component, "id").unwrap_or(state.sessions.len() as i64 + 1);
+                sessions.insert(id, state.sessions.len());
+                state.sessions.push(Session {
+                    id: id as i32,
+                    language: string(component, "lang"),
+                    source: string(component, "source"),
+                    filename: component.get("filename").and_then(Value::as_str).map(str::to_string),
+                    ..Default::default()
+                });
+            }
+            "tree" => {
+                if let Ok(tree) = serde_json::from_value::<Tree>(Value::Object(component.clone())) {
+                    trees.insert(tree.id as i64, state.trees.len());
+                    state.trees.push(tree);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Panes are attached once every editor and tree is known, whatever the layout order
+    let mut compilers_of = |state : &mut ClientState, owner : Option<Owner>| -> Option<(usize, bool)> {
+        match owner? {
+            Owner::Tree(id) => trees.get(&id).map(|&i| (i, true)),
+            Owner::Editor(id) => {
+                let index = *sessions.entry(id).or_insert_with(|| {
+                    state.sessions.push(Session { id: id as i32, ..Default::default() });
+                    state.sessions.len() - 1
+                });
+                Some((index, false))
+            }
+        }
+    };
+
+    for (name, component) in &found {
+        match name.as_str() {
+            "compiler" => {
+                let compiler = compiler_of(component);
+                match compilers_of(&mut state, owner(component, &["source"], &["tree"])) {
+                    Some((i, true)) => state.trees[i].compilers.push(compiler),
+                    Some((i, false)) => state.sessions[i].compilers.push(compiler),
+                    None => {}
+                }
+            }
+            "executor" => {
+                let executor = Executor {
+                    compiler_visible: flag(component, "compilationPanelShown"),
+                    compiler_output_visible: flag(component, "compilerOutShown"),
+                    arguments: string(component, "execArgs"),
+                    arguments_visible: flag(component, "argsPanelShown"),
+                    stdin: string(component, "execStdin"),
+                    stdin_visible: flag(component, "stdinPanelShown"),
+                    compiler: SessionCompiler { internal_id: None, ..compiler_of(component) },
+                    wrap: component.get("wrap").and_then(Value::as_bool),
+                    ..Default::default()
+                };
+                match compilers_of(&mut state, owner(component, &["source"], &["tree"])) {
+                    Some((i, true)) => state.trees[i].executors.push(executor),
+                    Some((i, false)) => state.sessions[i].executors.push(executor),
+                    None => {}
+                }
+            }
+            "conformance" => {
+                let view = ConformanceView {
+                    libs: libs(component),
+                    compilers: component.get("compilers").and_then(Value::as_array).map(|list| list.iter()
+                        .filter_map(Value::as_object)
+                        .map(|c| SessionCompiler {
+                            id: string(c, "compilerId"),
+                            options: string(c, "options"),
+                            ..Default::default()
+                        })
+                        .collect()).unwrap_or_default(),
+                    ..Default::default()
+                };
+                if let Some((i, false)) = compilers_of(&mut state, owner(component, &["editorid", "source"], &[])) {
+                    state.sessions[i].conformanceview = Some(view);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Tools and extra outputs refer to their compiler by its pane id
+    for (name, component) in &found {
+        let is_tool = name == "tool";
+        if !is_tool && !SPECIAL_OUTPUTS.contains(&name.as_str()) {
+            continue;
+        }
+        let pane = if is_tool { int(component, "compiler").or_else(|| int(component, "id")) } else { int(component, "id") };
+        let compiler = state.sessions.iter_mut().flat_map(|s| s.compilers.iter_mut())
+            .chain(state.trees.iter_mut().flat_map(|t| t.compilers.iter_mut()))
+            .find(|c| c.internal_id.is_some() && c.internal_id == pane);
+        let compiler = match compiler {
+            Some(compiler) => compiler,
+            None => continue,
+        };
+        if is_tool {
+            let id = match component.get("toolId").or_else(|| component.get("tool")).and_then(Value::as_str) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            compiler.tools.push(SessionTool { id, args: string(component, "args"), ..Default::default() });
+        } else if !compiler.specialoutputs.contains(name) {
+            compiler.specialoutputs.push(name.clone());
+        }
+    }
+
+    state
+}
+
+/// Builds a request for every compiler, executor and conformance view compiler in `state`,
+/// session by session, then tree by tree
+pub(crate) fn requests(gbolt : &Godbolt, state : &ClientState) -> Vec<CompilationRequest> {
+    let target = |id : &str, lang : &str| match gbolt.find_compiler_by_id(id) {
+        Some(c) => c.clone(),
+        None => Compiler { id: id.to_string(), lang: lang.to_string(), ..Default::default() },
+    };
+
+    let mut out = Vec::new();
+    for session in &state.sessions {
+        for compiler in &session.compilers {
+            let c = target(&compiler.id, &session.language);
+            out.push(CompilationRequest::new(&c, &session.source, options(compiler)));
+        }
+        // Libraries picked in a conformance view apply to each of its compilers
+        if let Some(view) = &session.conformanceview {
+            for compiler in &view.compilers {
+                let c = target(&compiler.id, &session.language);
+                let mut options = options(compiler);
+                for lib in view.libs.iter().map(LibraryRef::from) {
+                    if !options.libraries.contains(&lib) {
+                        options.libraries.push(lib);
+                    }
+                }
+                out.push(CompilationRequest::new(&c, &session.source, options));
+            }
+        }
+        for executor in &session.executors {
+            let c = target(&executor.compiler.id, &session.language);
+            out.push(CompilationRequest::new(&c, &session.source, executor_options(executor)));
+        }
+    }
+
+    for tree in &state.trees {
+        let included : Vec<SourceFile> = tree.files.iter()
+            .filter(|f| f.is_included)
+            .map(|f| SourceFile::new(&f.filename, &f.content))
+            .collect();
+        let main = tree.files.iter().find(|f| f.is_main_source && f.is_included);
+        let build = |c : &Compiler, options : RequestOptions| {
+            if tree.is_cmake_project {
+                return CompilationRequest::cmake(c, &included, &tree.cmake_args, options);
+            }
+            let mut req = match main {
+                Some(main) => CompilationRequest::new(c, &main.content, options).with_main_filename(&main.filename),
+                None => CompilationRequest::new(c, "", options),
+            };
+            for file in included.iter().filter(|f| main.is_none_or(|m| m.filename != f.filename)) {
+                req = req.with_file(&file.filename, &file.contents);
+            }
+            req
+        };
+        for compiler in &tree.compilers {
+            out.push(build(&target(&compiler.id, &tree.compiler_language_id), options(compiler)));
+        }
+        for executor in &tree.executors {
+            out.push(build(&target(&executor.compiler.id, &tree.compiler_language_id), executor_options(executor)));
+        }
+    }
+    out
+}
+
+fn options(compiler : &SessionCompiler) -> RequestOptions {
+    RequestOptions {
+        user_arguments: compiler.options.clone(),
+        filters: compiler.filters.clone().unwrap_or_default(),
+        libraries: compiler.libs.iter().map(LibraryRef::from).collect(),
+        tools: compiler.tools.iter().map(|t| ToolRequest::new(&t.id, &t.args)).collect(),
+        ..Default::default()
+    }
+}
+
+/// Executors compile without assembly output, then run the program
+fn executor_options(executor : &Executor) -> RequestOptions {
+    let mut options = options(&executor.compiler);
+    options.compiler_options.executor_request = true;
+    options.compiler_options.skip_asm = true;
+    options.filters.execute = Some(true);
+    options.execute_parameters.args = split_arguments(&executor.arguments);
+    options.execute_parameters.stdin = executor.stdin.clone();
+    options
+}
+
+/// Joins program arguments so that [`split_arguments`] gives them back
+pub(crate) fn join_arguments(args : &[String]) -> String {
+    args.iter().map(|arg| quote_argument(arg)).collect::<Vec<_>>().join(" ")
+}
+
+fn quote_argument(arg : &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c : char| c.is_whitespace() || c == '"' || c == '\'') {
+        return arg.to_string();
+    }
+    if !arg.contains('"') {
+        return format!("\"{}\"", arg);
+    }
+    if !arg.contains('\'') {
+        return format!("'{}'", arg);
+    }
+    // Both kinds of quote: adjacent quoted runs make one argument
+    let mut out = String::new();
+    let mut rest = arg;
+    while !rest.is_empty() {
+        let (quote, end) = match rest.find('"') {
+            Some(0) => ('\'', rest.find(|c| c != '"').unwrap_or(rest.len())),
+            Some(i) => ('"', i),
+            None => ('"', rest.len()),
+        };
+        out.push(quote);
+        out.push_str(&rest[..end]);
+        out.push(quote);
+        rest = &rest[end..];
+    }
+    out
+}
+
+/// Splits program arguments like a shell would, honouring single and double quotes
+pub(crate) fn split_arguments(args : &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut started = false;
+    for c in args.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if started {
+                    out.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        out.push(current);
+    }
+    out
+}
+
+/// Keeps error messages readable when a whole state is pasted
+fn shorten(s : &str) -> String {
+    match s.char_indices().nth(60) {
+        Some((at, _)) => format!("{}...", &s[..at]),
+        None => s.to_string(),
+    }
+}
@@ -0,0 +1,102 @@
+/// Alphabet of lz-string's `compressToBase64`
+const BASE64 : &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+
+/// Reverses lz-string's `compressToBase64` (https://github.com/pieroxy/lz-string), which
+/// Compiler Explorer uses for long URL fragments. `None` if the input is corrupted.
+pub(crate) fn decompress_from_base64(input : &str) -> Option<String> {
+    let values : Vec<u32> = input.bytes()
+        .map(|b| BASE64.iter().position(|&c| c == b).unwrap_or(0) as u32)
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    decompress(&values, 32)
+}
+
+/// Reads bits most significant first, `reset` being the top bit of each input value
+struct Bits<'a> {
+    values : &'a [u32],
+    val : u32,
+    position : u32,
+    reset : u32,
+    index : usize,
+}
+
+impl Bits<'_> {
+    fn read(&mut self, count : u32) -> u32 {
+        let mut bits = 0;
+        for power in 0..count {
+            let bit = self.val & self.position;
+            self.position >>= 1;
+            if self.position == 0 {
+                self.position = self.reset;
+                self.val = self.values.get(self.index).copied().unwrap_or(0);
+                self.index += 1;
+            }
+            if bit > 0 {
+                bits |= 1 << power;
+            }
+        }
+        bits
+    }
+}
+
+fn decompress(values : &[u32], reset : u32) -> Option<String> {
+    let mut bits = Bits { values, val: values[0], position: reset, reset, index: 1 };
+    // Codes 0, 1 and 2 mean an 8-bit char, a 16-bit char and the end of the stream
+    let mut dictionary : Vec<Vec<u16>> = vec![vec![], vec![], vec![]];
+    let mut enlarge_in : u32 = 4;
+    let mut num_bits : u32 = 3;
+
+    let first = match bits.read(2) {
+        0 => bits.read(8) as u16,
+        1 => bits.read(16) as u16,
+        _ => return Some(String::new()),
+    };
+    let mut w = vec![first];
+    dictionary.push(w.clone());
+    let mut result = w.clone();
+
+    loop {
+        if bits.index > values.len() {
+            return None;
+        }
+        let mut code = bits.read(num_bits) as usize;
+        match code {
+            0 | 1 => {
+                let c = bits.read(if code == 0 { 8 } else { 16 }) as u16;
+                dictionary.push(vec![c]);
+                code = dictionary.len() - 1;
+                enlarge_in -= 1;
+            }
+            2 => return Some(String::from_utf16_lossy(&result)),
+            _ => {}
+        }
+        if enlarge_in == 0 {
+            enlarge_in = 1 << num_bits;
+            num_bits += 1;
+        }
+
+        let entry = if code < dictionary.len() {
+            dictionary[code].clone()
+        } else if code == dictionary.len() {
+            let mut entry = w.clone();
+            entry.push(w[0]);
+            entry
+        } else {
+            return None;
+        };
+        result.extend_from_slice(&entry);
+
+        let mut added = w;
+        added.push(entry[0]);
+        dictionary.push(added);
+        enlarge_in -= 1;
+        w = entry;
+
+        if enlarge_in == 0 {
+            enlarge_in = 1 << num_bits;
+            num_bits += 1;
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ToolResult;
+
+/// Report llvm-mca prints for one code region
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct McaReport {
+    /// Region name, for files with `# LLVM-MCA-BEGIN name` markers
+    pub region : Option<String>,
+    pub iterations : u64,
+    /// Instructions simulated, across all iterations
+    pub instructions : u64,
+    pub total_cycles : u64,
+    pub total_uops : u64,
+    pub dispatch_width : u32,
+    pub uops_per_cycle : f64,
+    pub ipc : f64,
+    /// Reciprocal throughput of the whole block, in cycles per iteration
+    pub block_rthroughput : f64,
+    /// The instruction info table, one entry per instruction of the block
+    pub instruction_info : Vec<McaInstruction>,
+    /// Resources of the scheduling model, as (id, name) (i.e. ("2", "SKLPort0"))
+    pub resources : Vec<(String, String)>,
+    /// Cycles each resource is busy per iteration, in [`McaReport::resources`] order
+    pub resource_pressure : Vec<f64>,
+    /// Resource pressure broken down by instruction
+    pub pressure_by_instruction : Vec<InstructionPressure>,
+}
+
+/// A row of the instruction info table
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct McaInstruction {
+    pub uops : u32,
+    /// Latency in cycles
+    pub latency : u32,
+    /// Reciprocal throughput in cycles
+    pub rthroughput : f64,
+    pub may_load : bool,
+    pub may_store : bool,
+    pub has_side_effects : bool,
+    /// Instruction as printed, i.e. `mov eax, dword ptr [rdi]`
+    pub text : String,
+}
+
+/// A row of the resource pressure by instruction table
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct InstructionPressure {
+    /// Instruction as printed
+    pub text : String,
+    /// Cycles per iteration on each resource, in [`McaReport::resources`] order
+    pub pressure : Vec<f64>,
+}
+
+impl McaReport {
+    /// Parses llvm-mca's default output. With several code regions, only the first is parsed;
+    /// see [`McaReport::parse_regions`]. Returns `None` if there is no summary to parse.
+    pub fn parse(text : &str) -> Option<McaReport> {
+        McaReport::parse_regions(text).into_iter().next()
+    }
+
+    /// Parses every code region in llvm-mca's output, in order
+    pub fn parse_regions(text : &str) -> Vec<McaReport> {
+        let lines : Vec<&str> = text.lines().collect();
+        let mut starts : Vec<(usize, Option<String>)> = lines.iter().enumerate()
+            .filter_map(|(i, l)| region_header(l).map(|name| (i, name)))
+            .collect();
+        if starts.is_empty() {
+            starts.push((0, None));
+        }
+
+        let mut reports = Vec::new();
+        for (n, (start, name)) in starts.iter().enumerate() {
+            let end = starts.get(n + 1).map_or(lines.len(), |(next, _)| *next);
+            if let Some(mut report) = parse_region(&lines[*start..end]) {
+                report.region = name.clone();
+                reports.push(report);
+            }
+        }
+        reports
+    }
+
+    /// Parses the output of an llvm-mca tool run
+    pub fn from_tool(result : &ToolResult) -> Option<McaReport> {
+        McaReport::parse(&result.stdout_text())
+    }
+
+    /// Average cycles per iteration
+    pub fn cycles_per_iteration(&self) -> f64 {
+        if self.iterations == 0 {
+            return 0.0;
+        }
+        self.total_cycles as f64 / self.iterations as f64
+    }
+
+    /// Pressure per iteration on a resource, by name (i.e. SKLPort0)
+    pub fn pressure_on(&self, resource : &str) -> Option<f64> {
+        let index = self.resources.iter().position(|(_, name)| name == resource)?;
+        self.resource_pressure.get(index).copied()
+    }
+}
+
+/// `[0] Code Region` or `[1] Code Region - name`
+fn region_header(line : &str) -> Option<Option<String>> {
+    let rest = line.trim().strip_prefix('[')?;
+    let (index, rest) = rest.split_once(']')?;
+    if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let rest = rest.trim().strip_prefix("Code Region")?;
+    let name = rest.trim().trim_start_matches('-').trim();
+    Some(if name.is_empty() { None } else { Some(name.to_string()) })
+}
+
+fn parse_region(lines : &[&str]) -> Option<McaReport> {
+    let mut report = McaReport::default();
+    let mut found = false;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            let key = key.trim();
+            found |= key == "Iterations";
+            match key {
+                "Iterations" => report.iterations = value.parse().unwrap_or(0),
+                "Instructions" => report.instructions = value.parse().unwrap_or(0),
+                "Total Cycles" => report.total_cycles = value.parse().unwrap_or(0),
+                "Total uOps" => report.total_uops = value.parse().unwrap_or(0),
+                "Dispatch Width" => report.dispatch_width = value.parse().unwrap_or(0),
+                "uOps Per Cycle" => report.uops_per_cycle = number(value),
+                "IPC" => report.ipc = number(value),
+                "Block RThroughput" => report.block_rthroughput = number(value),
+                _ => {}
+            }
+        }
+
+        match line.trim() {
+            "Instruction Info:" => i = instruction_info(lines, i + 1, &mut report),
+            "Resources:" => i = resources(lines, i + 1, &mut report),
+            "Resource pressure per iteration:" => {
+                if let Some((columns, _)) = lines.get(i + 1).and_then(|h| header(h)) {
+                    if let Some(row) = lines.get(i + 2) {
+                        report.resource_pressure = columns.iter().map(|c| number(cell(row, c))).collect();
+                    }
+                }
+                i += 2;
+            }
+            "Resource pressure by instruction:" => i = pressure_by_instruction(lines, i + 1, &mut report),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if found { Some(report) } else { None }
+}
+
+/// Reads the `[1]: #uOps` legend and the table below it, returning where the table ends
+fn instruction_info(lines : &[&str], mut i : usize, report : &mut McaReport) -> usize {
+    let mut legend : Vec<(String, String)> = Vec::new();
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if let Some(rest) = line.strip_prefix('[') {
+            if let Some((id, name)) = rest.split_once("]:") {
+                legend.push((id.to_string(), name.trim().to_string()));
+                i += 1;
+                continue;
+            }
+        }
+        if line.is_empty() {
+            i += 1;
+            continue;
+        }
+        break;
+    }
+
+    let (columns, text_at) = match lines.get(i).and_then(|h| header(h)) {
+        Some(header) => header,
+        None => return i,
+    };
+    let column_of = |name : &str| legend.iter()
+        .find(|(_, n)| n.starts_with(name))
+        .and_then(|(id, _)| columns.iter().find(|c| &c.0 == id));
+
+    i += 1;
+    while i < lines.len() && !lines[i].trim().is_empty() {
+        let row = lines[i];
+        let flag = |name : &str| column_of(name).is_some_and(|c| !cell(row, c).is_empty());
+        report.instruction_info.push(McaInstruction {
+            uops: column_of("#uOps").map_or(0, |c| cell(row, c).parse().unwrap_or(0)),
+            latency: column_of("Latency").map_or(0, |c| cell(row, c).parse().unwrap_or(0)),
+            rthroughput: column_of("RThroughput").map_or(0.0, |c| number(cell(row, c))),
+            may_load: flag("MayLoad"),
+            may_store: flag("MayStore"),
+            has_side_effects: flag("HasSideEffects"),
+            text: text_from(row, text_at),
+        });
+        i += 1;
+    }
+    i
+}
+
+/// Reads `[0]   - SKLDivider` lines, returning where they end
+fn resources(lines : &[&str], mut i : usize, report : &mut McaReport) -> usize {
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let parsed = line.strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .and_then(|(id, rest)| rest.trim().strip_prefix('-').map(|name| (id.to_string(), name.trim().to_string())));
+        match parsed {
+            Some(resource) => report.resources.push(resource),
+            None if line.is_empty() && report.resources.is_empty() => {}
+            None => break,
+        }
+        i += 1;
+    }
+    i
+}
+
+fn pressure_by_instruction(lines : &[&str], mut i : usize, report : &mut McaReport) -> usize {
+    let (columns, text_at) = match lines.get(i).and_then(|h| header(h)) {
+        Some(header) => header,
+        None => return i,
+    };
+    i += 1;
+    while i < lines.len() && !lines[i].trim().is_empty() {
+        let row = lines[i];
+        report.pressure_by_instruction.push(InstructionPressure {
+            text: text_from(row, text_at),
+            pressure: columns.iter().map(|c| number(cell(row, c))).collect(),
+        });
+        i += 1;
+    }
+    i
+}
+
+/// A table column: its id and the byte range it spans in the header, open-ended for the last one
+type Column = (String, usize, usize);
+
+/// Splits a table header such as `[1]    [2]    Instructions:` into columns. Cells are
+/// read from the column's start to the next column's start, since llvm-mca right-aligns
+/// some values and leaves flags blank. Also returns where the instruction text starts.
+fn header(line : &str) -> Option<(Vec<Column>, Option<usize>)> {
+    let mut columns : Vec<Column> = Vec::new();
+    let mut at = 0;
+    while let Some(open) = line[at..].find('[') {
+        let open = at + open;
+        let close = open + line[open..].find(']')?;
+        if let Some(last) = columns.last_mut() {
+            last.2 = open;
+        }
+        columns.push((line[open + 1..close].to_string(), open, usize::MAX));
+        at = close + 1;
+    }
+    let text_at = line.find("Instructions:");
+    if let (Some(text_at), Some(last)) = (text_at, columns.last_mut()) {
+        last.2 = text_at;
+    }
+    if columns.is_empty() { None } else { Some((columns, text_at)) }
+}
+
+/// Text of `row` under `column`. Values may start a little before the column header
+/// when right-aligned, so the range is widened to the start of the column's gap.
+fn cell<'a>(row : &'a str, column : &Column) -> &'a str {
+    let start = column.1.saturating_sub(1).min(row.len());
+    let end = column.2.saturating_sub(1).max(start).min(row.len());
+    match row.get(start..end) {
+        Some(cell) => cell.trim(),
+        None => "",
+    }
+}
+
+fn text_from(row : &str, at : Option<usize>) -> String {
+    match at.and_then(|at| row.get(at..)) {
+        Some(text) => text.trim().replace('\t', " "),
+        None => String::new(),
+    }
+}
+
+/// Parses a number, reading `-` (unused) as zero
+fn number(s : &str) -> f64 {
+    s.trim().parse().unwrap_or(0.0)
+}
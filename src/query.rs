@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::{Bound, RangeBounds};
+
+use crate::{Compiler, CompilerVersion, Godbolt, Version};
+use crate::version::{normalize_arch, normalize_family};
+
+/// Composable filter over the cached compilers, evaluated with [`Godbolt::query`].
+/// Every criterion left unset matches all compilers.
+#[derive(Clone, Debug, Default)]
+pub struct CompilerQuery {
+    language : Option<String>,
+    arch : Option<String>,
+    family : Option<String>,
+    versions : Option<(Bound<Version>, Bound<Version>)>,
+    can_execute : Option<bool>,
+    supports_binary : Option<bool>,
+    text : Option<String>,
+    page : usize,
+    per_page : Option<usize>,
+}
+
+/// One page of [`CompilerQuery`] results
+#[derive(Clone, Debug)]
+pub struct QueryPage<'a> {
+    /// Compilers on this page
+    pub items : Vec<&'a Compiler>,
+    /// Number of matching compilers across all pages
+    pub total : usize,
+    /// Zero-based index of this page
+    pub page : usize,
+    /// Number of pages
+    pub pages : usize,
+}
+
+impl CompilerQuery {
+    pub fn new() -> Self {
+        CompilerQuery::default()
+    }
+
+    /// Language id, name or extension
+    pub fn language(mut self, language : &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    /// Target architecture (i.e. x86_64, aarch64; amd64 and x64 are understood too)
+    pub fn arch(mut self, arch : &str) -> Self {
+        self.arch = Some(normalize_arch(arch).unwrap_or_else(|| arch.to_lowercase()));
+        self
+    }
+
+    /// Compiler family (i.e. gcc, clang, msvc)
+    pub fn family(mut self, family : &str) -> Self {
+        self.family = Some(normalize_family(family));
+        self
+    }
+
+    /// Released versions in `range`. Trunk builds never match.
+    pub fn versions<R : RangeBounds<Version>>(mut self, range : R) -> Self {
+        self.versions = Some((range.start_bound().cloned(), range.end_bound().cloned()));
+        self
+    }
+
+    /// Whether compiled programs can be run
+    pub fn can_execute(mut self, can_execute : bool) -> Self {
+        self.can_execute = Some(can_execute);
+        self
+    }
+
+    /// Whether output can be disassembled from a binary
+    pub fn supports_binary(mut self, supports_binary : bool) -> Self {
+        self.supports_binary = Some(supports_binary);
+        self
+    }
+
+    /// Every whitespace separated word must appear in the id, name or an alias, ignoring case
+    pub fn text(mut self, text : &str) -> Self {
+        self.text = Some(text.to_lowercase());
+        self
+    }
+
+    /// Returns page `page` (zero-based) of `per_page` results
+    pub fn page(mut self, page : usize, per_page : usize) -> Self {
+        self.page = page;
+        self.per_page = Some(per_page.max(1));
+        self
+    }
+
+    fn matches(&self, compiler : &Compiler, info : &CompilerVersion) -> bool {
+        if self.arch.is_some() && info.arch != self.arch {
+            return false;
+        }
+        if let Some(family) = &self.family {
+            if &info.family != family {
+                return false;
+            }
+        }
+        if let Some(range) = &self.versions {
+            match info.version {
+                Some(v) if !info.trunk && range.contains(&v) => {}
+                _ => return false,
+            }
+        }
+        if let Some(wanted) = self.can_execute {
+            if (compiler.supports_execute == Some(true)) != wanted {
+                return false;
+            }
+        }
+        if let Some(wanted) = self.supports_binary {
+            if (compiler.supports_binary == Some(true)) != wanted {
+                return false;
+            }
+        }
+        if let Some(text) = &self.text {
+            let haystack = format!("{} {} {}", compiler.id, compiler.name, compiler.alias.join(" ")).to_lowercase();
+            if !text.split_whitespace().all(|word| haystack.contains(word)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Runs the query. Results are sorted by language, then family, then newest
+    /// version first (trunk builds ahead of releases), then name.
+    pub(crate) fn run<'a>(&self, gbolt : &'a Godbolt) -> QueryPage<'a> {
+        let scope = match &self.language {
+            Some(language) => match gbolt.find_language_by_id(language) {
+                Some(lang) => Some(lang.id.as_str()),
+                None => return self.paginate(Vec::new()),
+            },
+            None => None,
+        };
+
+        let mut seen = HashSet::new();
+        let mut found : Vec<(&Compiler, CompilerVersion)> = gbolt.cache.iter()
+            .filter(|e| scope.is_none_or(|id| e.language.id == id))
+            .flat_map(|e| e.compilers.iter())
+            .filter(|c| seen.insert(c.id.as_str()))
+            .map(|c| (c, c.version_info()))
+            .filter(|(c, info)| self.matches(c, info))
+            .collect();
+
+        found.sort_by(|(a, va), (b, vb)| a.lang.cmp(&b.lang)
+            .then_with(|| va.family.cmp(&vb.family))
+            .then_with(|| match (va.trunk, vb.trunk) {
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                _ => vb.version.cmp(&va.version),
+            })
+            .then_with(|| a.name.cmp(&b.name)));
+
+        self.paginate(found.into_iter().map(|(c, _)| c).collect())
+    }
+
+    fn paginate<'a>(&self, all : Vec<&'a Compiler>) -> QueryPage<'a> {
+        let total = all.len();
+        let per_page = self.per_page.unwrap_or_else(|| total.max(1));
+        let items = all.into_iter().skip(self.page.saturating_mul(per_page)).take(per_page).collect();
+        QueryPage {
+            items,
+            total,
+            page: self.page,
+            pages: total.div_ceil(per_page),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{AsmResult, GodboltResponse};
+
+/// Foreground colours given to source lines in turn, as Compiler Explorer's colourised view does
+const PALETTE : [u8; 6] = [31, 32, 33, 34, 35, 36];
+const RESET : &str = "\x1b[0m";
+const TAB_WIDTH : usize = 8;
+
+/// How [`GodboltResponse::render_interleaved`] lays out its text
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Colour each source line and its assembly with ANSI escapes
+    pub colour : bool,
+    /// Prefix source lines with their line number
+    pub line_numbers : bool,
+    /// Cut lines longer than this many characters, ending them with `…`
+    pub width : Option<usize>,
+}
+
+impl GodboltResponse {
+    /// Renders the assembly grouped under the source lines that generated it.
+    /// `source` is the main source the request was compiled from.
+    pub fn render_interleaved(&self, source : &str, options : &RenderOptions) -> String {
+        interleave(source, self.asm_lines(), options)
+    }
+}
+
+pub(crate) fn interleave(source : &str, asm : &[AsmResult], options : &RenderOptions) -> String {
+    let source : Vec<&str> = source.lines().collect();
+    let number_width = asm.iter()
+        .filter_map(AsmResult::source_line)
+        .max()
+        .map_or(1, |n| n.to_string().len());
+
+    let mut out = String::new();
+    let mut current = None;
+    for line in asm {
+        let source_line = line.source_line();
+        if let Some(n) = source_line.filter(|_| source_line != current) {
+            let text = n.checked_sub(1).and_then(|i| source.get(i as usize)).copied().unwrap_or("");
+            let header = if options.line_numbers {
+                format!("{:>w$} | {}", n, text, w = number_width)
+            } else {
+                format!("| {}", text)
+            };
+            push_line(&mut out, &header, Some(n), options);
+        }
+        current = source_line;
+        push_line(&mut out, line.text(), source_line, options);
+    }
+    out
+}
+
+fn push_line(out : &mut String, text : &str, source_line : Option<u32>, options : &RenderOptions) {
+    let mut text = expand_tabs(text.trim_end());
+    if let Some(width) = options.width.filter(|&w| w > 0) {
+        if text.chars().count() > width {
+            text = text.chars().take(width - 1).collect();
+            text.push('…');
+        }
+    }
+    match source_line {
+        Some(n) if options.colour => {
+            let colour = PALETTE[n as usize % PALETTE.len()];
+            out.push_str(&format!("\x1b[{}m{}{}", colour, text, RESET));
+        }
+        _ => out.push_str(&text),
+    }
+    out.push('\n');
+}
+
+/// Tabs in assembly would throw off the width limit
+fn expand_tabs(text : &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - column % TAB_WIDTH;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            out.push(c);
+            column += 1;
+        }
+    }
+    out
+}
@@ -0,0 +1,82 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::GodboltError;
+
+/// How failed requests are retried. Retries are opt-in: a [`Godbolt`](crate::Godbolt)
+/// built without a policy makes exactly one attempt per call.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts : u32,
+    /// Delay before the first retry
+    pub initial_backoff : Duration,
+    /// Upper bound for the computed backoff
+    pub max_backoff : Duration,
+    /// Factor the backoff grows by after every attempt
+    pub multiplier : f64,
+    /// Randomize each delay between half and all of the computed backoff
+    pub jitter : bool,
+    /// Wait at least as long as the server's `Retry-After` header asks
+    pub respect_retry_after : bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: true,
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Whether an error is worth another attempt: rate limiting, gateway
+    /// errors and connection failures or timeouts
+    pub fn is_retryable(error : &GodboltError) -> bool {
+        match error {
+            GodboltError::RateLimited { .. } => true,
+            GodboltError::Http { status, .. } => matches!(status, 502..=504),
+            GodboltError::Transport(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Delay before the attempt following `attempt` (1-based), given the error it failed with
+    pub fn delay(&self, attempt : u32, error : &GodboltError) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        // `max` also turns a NaN backoff into zero
+        let mut delay = Duration::from_secs_f64(backoff.max(0.0).min(self.max_backoff.as_secs_f64()));
+
+        if self.jitter {
+            let fraction = (random() % 1000) as f64 / 1000.0;
+            delay = delay.mul_f64(0.5 + fraction / 2.0);
+        }
+
+        if self.respect_retry_after {
+            if let GodboltError::RateLimited { retry_after: Some(wait) } = error {
+                delay = delay.max(*wait);
+            }
+        }
+        delay
+    }
+}
+
+/// A random number from the std hasher's per-instance keys
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
@@ -0,0 +1,152 @@
+use serde_json::{Map, Number, Value};
+
+/// Characters that cannot appear in a bare identifier
+const NOT_IDCHAR : &str = " '!:(),*@$";
+
+/// Decodes rison (https://github.com/Nanonid/rison), the JSON dialect Compiler Explorer
+/// uses in URL fragments, into JSON. `s` must already be URL-decoded.
+pub(crate) fn decode(s : &str) -> Result<Value, String> {
+    let mut parser = Parser { chars: s.chars().collect(), at: 0 };
+    let value = parser.value()?;
+    if parser.at != parser.chars.len() {
+        return Err(format!("unexpected `{}` at {}", parser.chars[parser.at], parser.at));
+    }
+    Ok(value)
+}
+
+/// Decodes O-rison, an object without its surrounding parentheses (`a:1,b:2`)
+pub(crate) fn decode_object(s : &str) -> Result<Value, String> {
+    decode(&format!("({})", s))
+}
+
+struct Parser {
+    chars : Vec<char>,
+    at : usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or_else(|| "unexpected end of input".to_string())?;
+        self.at += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected : char) -> Result<(), String> {
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(format!("expected `{}` but found `{}` at {}", expected, c, self.at - 1)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('(') => self.object(),
+            Some('!') => {
+                self.at += 1;
+                match self.next()? {
+                    '(' => self.array(),
+                    't' => Ok(Value::Bool(true)),
+                    'f' => Ok(Value::Bool(false)),
+                    'n' => Ok(Value::Null),
+                    c => Err(format!("unknown literal `!{}` at {}", c, self.at - 2)),
+                }
+            }
+            Some('\'') => self.string().map(Value::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => self.id().map(Value::String),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('(')?;
+        let mut map = Map::new();
+        if self.peek() == Some(')') {
+            self.at += 1;
+            return Ok(Value::Object(map));
+        }
+        loop {
+            let key = match self.peek() {
+                Some('\'') => self.string()?,
+                _ => self.id()?,
+            };
+            self.expect(':')?;
+            let value = self.value()?;
+            map.insert(key, value);
+            match self.next()? {
+                ',' => continue,
+                ')' => return Ok(Value::Object(map)),
+                c => return Err(format!("expected `,` or `)` but found `{}` at {}", c, self.at - 1)),
+            }
+        }
+    }
+
+    /// The leading `!(` is already consumed
+    fn array(&mut self) -> Result<Value, String> {
+        let mut items = Vec::new();
+        if self.peek() == Some(')') {
+            self.at += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.next()? {
+                ',' => continue,
+                ')' => return Ok(Value::Array(items)),
+                c => return Err(format!("expected `,` or `)` but found `{}` at {}", c, self.at - 1)),
+            }
+        }
+    }
+
+    /// `'...'`, where `!'` is a quote and `!!` an exclamation mark
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut out = String::new();
+        loop {
+            match self.next()? {
+                '\'' => return Ok(out),
+                '!' => match self.next()? {
+                    c @ ('\'' | '!') => out.push(c),
+                    c => return Err(format!("invalid string escape `!{}` at {}", c, self.at - 2)),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn id(&mut self) -> Result<String, String> {
+        let start = self.at;
+        while let Some(c) = self.peek() {
+            if NOT_IDCHAR.contains(c) {
+                break;
+            }
+            self.at += 1;
+        }
+        if self.at == start {
+            return Err(format!("expected a value at {}", start));
+        }
+        Ok(self.chars[start..self.at].iter().collect())
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.at;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                break;
+            }
+            self.at += 1;
+        }
+        let text : String = self.chars[start..self.at].iter().collect();
+        if let Ok(n) = text.parse::<i64>() {
+            return Ok(Value::Number(n.into()));
+        }
+        text.parse::<f64>().ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("invalid number `{}` at {}", text, start))
+    }
+}
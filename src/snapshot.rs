@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::*;
+
+use crate::{Compiler, Format, GodboltError, Language, Library};
+
+/// Everything a [`Godbolt`](crate::Godbolt) caches, in a form that can be written to disk
+/// and loaded again without touching the network
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct GodboltSnapshot {
+    /// Seconds since the Unix epoch when the data was fetched
+    pub created_at : u64,
+    /// Base URL of the instance the data was fetched from
+    pub base_url : String,
+    /// All languages
+    pub languages : Vec<Language>,
+    /// All compilers, of every language
+    pub compilers : Vec<Compiler>,
+    /// All formatting tools
+    pub formats : Vec<Format>,
+    /// Libraries, keyed by language id. Only languages whose libraries were loaded are present.
+    pub libraries : BTreeMap<String, Vec<Library>>,
+}
+
+impl GodboltSnapshot {
+    /// Reads a snapshot from a JSON file
+    pub fn load<P : AsRef<Path>>(path : P) -> Result<Self, GodboltError> {
+        let text = fs::read_to_string(path)?;
+        match serde_json::from_str::<GodboltSnapshot>(&text) {
+            Ok(snapshot) => Ok(snapshot),
+            Err(e) => Err(GodboltError::deserialize(e, &text)),
+        }
+    }
+
+    /// Writes the snapshot as JSON. The file is replaced atomically, so readers
+    /// never observe a partially written snapshot.
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> Result<(), GodboltError> {
+        let path = path.as_ref();
+        let json = match serde_json::to_string(self) {
+            Ok(json) => json,
+            Err(e) => return Err(GodboltError::Serialize(e)),
+        };
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Time since the data was fetched
+    pub fn age(&self) -> Duration {
+        let created = UNIX_EPOCH + Duration::from_secs(self.created_at);
+        SystemTime::now().duration_since(created).unwrap_or_default()
+    }
+
+    /// Whether the data is older than `ttl`
+    pub fn is_stale(&self, ttl : Duration) -> bool {
+        self.age() > ttl
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
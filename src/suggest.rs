@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::Compiler;
+
+/// Scores below this are not worth suggesting
+const MIN_SCORE : f64 = 0.35;
+
+/// A compiler that closely matches an unknown name
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    /// Compiler id
+    pub id : String,
+    /// Compiler display name
+    pub name : String,
+    /// Language id of the compiler
+    pub lang : String,
+    /// How close the match is, from 0 (unrelated) to 1 (identical)
+    pub score : f64,
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.id, self.name)
+    }
+}
+
+/// Ranks `compilers` by similarity to `target`, best first
+pub(crate) fn rank<'a, I>(target : &str, compilers : I, limit : usize) -> Vec<Suggestion>
+    where I : Iterator<Item = &'a Compiler> {
+    let query = target.trim().to_lowercase();
+    let query_tokens = tokens(&query);
+
+    let mut ranked : Vec<Suggestion> = compilers
+        .map(|c| Suggestion {
+            id: c.id.clone(),
+            name: c.name.clone(),
+            lang: c.lang.clone(),
+            score: score(&query, &query_tokens, c),
+        })
+        .filter(|s| s.score >= MIN_SCORE)
+        .collect();
+
+    ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal).then_with(|| a.id.cmp(&b.id)));
+    ranked.dedup_by(|a, b| a.id == b.id);
+    ranked.truncate(limit);
+    ranked
+}
+
+/// Blends edit distance against the id and aliases with how many of the query's
+/// tokens show up in the compiler's id, aliases and display name
+fn score(query : &str, query_tokens : &[String], compiler : &Compiler) -> f64 {
+    let names = std::iter::once(&compiler.id).chain(compiler.alias.iter());
+    let edit = names
+        .map(|n| similarity(query, &n.to_lowercase()))
+        .fold(0.0, f64::max);
+
+    if query_tokens.is_empty() {
+        return edit;
+    }
+
+    let mut candidate_tokens = tokens(&compiler.name.to_lowercase());
+    candidate_tokens.extend(tokens(&compiler.id.to_lowercase()));
+    // "13.2" is typed as "132" at least as often as it is typed with the dot
+    let digits : String = compiler.name.chars().filter(char::is_ascii_digit).collect();
+    candidate_tokens.push(digits);
+
+    let matched = query_tokens.iter()
+        .filter(|q| candidate_tokens.iter().any(|c| token_matches(q, c)))
+        .count();
+    let token = matched as f64 / query_tokens.len() as f64;
+
+    (edit + token) / 2.0
+}
+
+/// Tokens match when equal, or when one is a prefix of the other and at least two characters long
+fn token_matches(a : &str, b : &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    short == long || (short.len() >= 2 && long.starts_with(short))
+}
+
+/// Splits into runs of letters and runs of digits: "x86-64 gcc13.2" -> x, 86, 64, gcc, 13, 2
+fn tokens(s : &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut digits = false;
+    for ch in s.chars() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                out.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if !current.is_empty() && ch.is_ascii_digit() != digits {
+            out.push(std::mem::take(&mut current));
+        }
+        digits = ch.is_ascii_digit();
+        current.push(ch);
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+/// 1 minus the Levenshtein distance, normalized by the longer string's length
+fn similarity(a : &str, b : &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+fn levenshtein(a : &str, b : &str) -> usize {
+    let b : Vec<char> = b.chars().collect();
+    let mut prev : Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
@@ -1,2 +1,5 @@
 #[cfg(test)]
-pub mod tests;
\ No newline at end of file
+pub mod server;
+#[cfg(test)]
+#[allow(clippy::module_inception, clippy::len_zero)]
+pub mod tests;
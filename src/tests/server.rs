@@ -0,0 +1,151 @@
+//! Minimal HTTP stand-in for a Compiler Explorer instance, so tests can run
+//! without reaching godbolt.org.
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request as seen by the stand-in server
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method : String,
+    pub path : String,
+    pub headers : Vec<(String, String)>,
+    pub body : String,
+}
+
+impl Recorded {
+    pub fn header(&self, name : &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A canned response returned by the stand-in server
+pub struct Reply {
+    pub status : u16,
+    pub headers : Vec<(String, String)>,
+    pub body : String,
+}
+
+impl Reply {
+    pub fn json(body : &str) -> Reply {
+        Reply { status: 200, headers: vec![], body: body.to_string() }
+    }
+
+    pub fn status(status : u16, body : &str) -> Reply {
+        Reply { status, headers: vec![], body: body.to_string() }
+    }
+
+    pub fn with_header(mut self, name : &str, value : &str) -> Reply {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = Arc<dyn Fn(&Recorded) -> Reply + Send + Sync>;
+
+pub struct StandIn {
+    /// Base URL of the server, including the given path prefix
+    pub url : String,
+    /// Every request received so far
+    pub requests : Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl StandIn {
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Starts a server on a random local port. The handler sees request paths
+/// with `prefix` already stripped.
+pub async fn serve<F>(prefix : &str, handler : F) -> StandIn
+    where F: Fn(&Recorded) -> Reply + Send + Sync + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let handler : Handler = Arc::new(handler);
+
+    let prefix = prefix.trim_end_matches('/').to_string();
+    let url = format!("http://{}{}", addr, prefix);
+    let log = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(_) => return,
+            };
+            let handler = handler.clone();
+            let log = log.clone();
+            let prefix = prefix.clone();
+            tokio::spawn(async move {
+                let _ = handle(stream, &prefix, handler, log).await;
+            });
+        }
+    });
+
+    StandIn { url, requests }
+}
+
+async fn handle(mut stream : TcpStream, prefix : &str, handler : Handler, log : Arc<Mutex<Vec<Recorded>>>) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let full_path = request_line.next().unwrap_or_default();
+    let path = full_path.strip_prefix(prefix).unwrap_or(full_path).to_string();
+    let headers : Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let length = headers.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = buf[head_end..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    let recorded = Recorded {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    };
+    log.lock().unwrap().push(recorded.clone());
+    let reply = handler(&recorded);
+
+    let mut out = format!(
+        "HTTP/1.1 {} Stand-In\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reply.status, reply.body.len()
+    );
+    for (k, v) in &reply.headers {
+        out.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    out.push_str("\r\n");
+    out.push_str(&reply.body);
+    stream.write_all(out.as_bytes()).await?;
+    stream.shutdown().await
+}
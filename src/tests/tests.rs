@@ -4,28 +4,32 @@ use std::error::Error;
 
 #[tokio::test]
 async fn get_languages() -> Result<(), Box<dyn Error>> {
-    let langs = Godbolt::get_languages().await?;
+    let gbolt = Godbolt::new().await?;
+    let langs = gbolt.get_languages().await?;
     assert!(langs.len() > 0);
     Ok(())
 }
 
 #[tokio::test]
 async fn get_compilers() -> Result<(), Box<dyn Error>> {
-   let compilers = Godbolt::get_compilers().await?;
+    let gbolt = Godbolt::new().await?;
+    let compilers = gbolt.get_compilers().await?;
     assert!(compilers.len() > 0);
     Ok(())
 }
 
 #[tokio::test]
 async fn get_compilers_for() -> Result<(), Box<dyn Error>> {
-    let compilers = Godbolt::get_compilers_for("c++").await?;
+    let gbolt = Godbolt::new().await?;
+    let compilers = gbolt.get_compilers_for("c++").await?;
     assert!(compilers.len() > 0);
     Ok(())
 }
 
 #[tokio::test]
 async fn get_libraries_for() -> Result<(), Box<dyn Error>> {
-    let libs = Godbolt::get_libraries_for("c").await?;
+    let gbolt = Godbolt::new().await?;
+    let libs = gbolt.get_libraries_for("c").await?;
     assert!(libs.len() > 0);
     Ok(())
 }
@@ -37,6 +41,7 @@ async fn base64() -> Result<(), Box<dyn Error>> {
 
     let filters = CompilationFilters {
         binary: None,
+        binary_object: None,
         comment_only: Some(true),
         demangle: Some(true),
         directives: Some(true),
@@ -44,7 +49,9 @@ async fn base64() -> Result<(), Box<dyn Error>> {
         intel: Some(true),
         labels: Some(true),
         library_code: None,
-        trim: None
+        trim: None,
+        debug_calls: None,
+        extra: Default::default(),
     };
 
     let opts = RequestOptions {
@@ -53,12 +60,15 @@ async fn base64() -> Result<(), Box<dyn Error>> {
             skip_asm: false,
             executor_request: true,
             produce_pp: None,
+            cmake_args: None,
         },
         execute_parameters: ExecuteParameters {
             args: vec![String::from("awd")],
             stdin: "teststdin".to_string(),
         },
         filters,
+        libraries: vec![],
+        tools: vec![],
     };
     let str = Godbolt::get_base64(&c, "#include <iostream>\nint main() {\nstd::cout << \"😂\";\n}", opts)?;
     assert!(str.len() > 0);
@@ -83,7 +93,7 @@ async fn format_test() -> Result<(), Box<dyn Error>> {
 
 #[tokio::test]
 async fn compilation_attempt() -> Result<(), Box<dyn Error>> {
-    let gbolt = Godbolt::new().await?;
+    let gbolt = Godbolt::builder().user_agent("godbolt-rs-test").build().await?;
     let c = gbolt.resolve("clang1000");
     assert!(c.is_some());
     let compiler = c.unwrap();
@@ -94,22 +104,25 @@ async fn compilation_attempt() -> Result<(), Box<dyn Error>> {
             skip_asm: true,
             executor_request: true,
             produce_pp: None,
+            cmake_args: None,
         },
         execute_parameters: ExecuteParameters {
             args: vec![],
             stdin: String::from(""),
         },
         filters: CompilationFilters::default(),
+        libraries: vec![],
+        tools: vec![],
     };
 
-    let res = Godbolt::send_request(&compiler, "int main(void) {return 0;}", options, "godbolt-rs-test").await;
+    let res = gbolt.send_request(&compiler, "int main(void) {return 0;}", options).await;
     assert!(res.is_ok());
     Ok(())
 }
 
 #[tokio::test]
 async fn compilation_attempt2() -> Result<(), Box<dyn Error>> {
-    let gbolt = Godbolt::new().await?;
+    let gbolt = Godbolt::builder().user_agent("godbolt-rs-test").build().await?;
     let c = gbolt.resolve("vc_v19_latest_x64");
     assert!(c.is_some());
     let compiler = c.unwrap();
@@ -120,22 +133,25 @@ async fn compilation_attempt2() -> Result<(), Box<dyn Error>> {
             skip_asm: true,
             executor_request: true,
             produce_pp: None,
+            cmake_args: None,
         },
         execute_parameters: ExecuteParameters {
             args: vec![],
             stdin: String::from(""),
         },
         filters: CompilationFilters::default(),
+        libraries: vec![],
+        tools: vec![],
     };
 
-    let res = Godbolt::send_request(&compiler, "void foo(void) {}", options, "godbolt-rs-test").await;
+    let res = gbolt.send_request(&compiler, "void foo(void) {}", options).await;
     assert!(res.is_ok());
     Ok(())
 }
 #[tokio::test]
 async fn produce_pp_filter_headers() -> Result<(), Box<dyn Error>> {
     use crate::ProducePp;
-    let gbolt = Godbolt::new().await?;
+    let gbolt = Godbolt::builder().user_agent("godbolt-rs-test").build().await?;
     let compiler = gbolt.resolve("g142").expect("g142 should resolve");
 
     let options = RequestOptions {
@@ -144,16 +160,18 @@ async fn produce_pp_filter_headers() -> Result<(), Box<dyn Error>> {
             skip_asm: true,
             executor_request: false,
             produce_pp: Some(ProducePp { filter_headers: true, clang_format: false }),
+            cmake_args: None,
         },
         execute_parameters: ExecuteParameters::default(),
         filters: CompilationFilters::default(),
+        libraries: vec![],
+        tools: vec![],
     };
 
-    let res = Godbolt::send_request(
+    let res = gbolt.send_request(
         &compiler,
         "#include <stdio.h>\nint main() { printf(\"hi\"); return 0; }",
         options,
-        "godbolt-rs-test",
     ).await?;
 
     let pp = res.pp_output.expect("ppOutput should be present");
@@ -164,3 +182,1265 @@ async fn produce_pp_filter_headers() -> Result<(), Box<dyn Error>> {
     assert!(!pp.output.contains("extern"), "header bodies should be filtered out");
     Ok(())
 }
+
+#[tokio::test]
+async fn custom_base_url() -> Result<(), Box<dyn Error>> {
+    use crate::tests::server::{serve, Reply};
+    let server = serve("/explorer/", |req| match req.path.split('?').next().unwrap() {
+        "/api/formats" => Reply::json("[]"),
+        "/api/languages" => Reply::json(r#"[{"id":"c++","name":"C++","extensions":[".cpp"],"monaco":"cppp","defaultCompiler":"g132"}]"#),
+        "/api/compilers" => Reply::json(r#"[{"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":[]}]"#),
+        _ => Reply::status(404, "not found"),
+    }).await;
+
+    let gbolt = Godbolt::with_base_url(&format!("{}/", server.url)).await?;
+    assert_eq!(gbolt.base_url(), server.url);
+    let c = gbolt.resolve("c++").expect("default compiler should resolve");
+    assert_eq!(c.id, "g132");
+    assert_eq!(server.requests().len(), 3);
+
+    let link = gbolt.get_link(&c, "int main() {}", RequestOptions::default())?;
+    assert!(link.starts_with(&format!("{}/clientstate/", server.url)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn builder_shares_configured_client() -> Result<(), Box<dyn Error>> {
+    use std::time::Duration;
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |req| match req.path.split('?').next().unwrap() {
+        "/api/formats" => Reply::json(r#"[{"exe":"/opt/clang-format","version":"17","name":"clangformat","styles":["Google"],"type":"clangformat"}]"#),
+        "/api/format/clangformat" => Reply::json(r#"{"exit":0,"answer":"int x;"}"#),
+        _ => Reply::json("[]"),
+    }).await;
+
+    let gbolt = Godbolt::builder()
+        .base_url(&server.url)
+        .user_agent("godbolt-rs-test")
+        .header("X-Team", "compilers")
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(10))
+        .build()
+        .await?;
+    assert_eq!(gbolt.formats.len(), 1);
+
+    let res = gbolt.format_code("clangformat", "Google", "int  x ;", true, 4).await?;
+    assert_eq!(res.answer, "int x;");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    for req in &requests {
+        assert_eq!(req.header("user-agent"), Some("godbolt-rs-test"));
+        assert_eq!(req.header("x-team"), Some("compilers"));
+        assert_eq!(req.header("accept"), Some("application/json"));
+    }
+    let format = requests.last().unwrap();
+    assert_eq!(format.method, "POST");
+    assert!(format.body.contains(r#""useSpaces":true"#));
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_are_structured() -> Result<(), Box<dyn Error>> {
+    use crate::GodboltError;
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |req| match req.path.split('?').next().unwrap() {
+        "/api/languages" => Reply::status(429, "slow down").with_header("Retry-After", "7"),
+        "/api/formats" => Reply::status(503, "maintenance"),
+        "/api/compilers" => Reply::json(r#"[{"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":null}]"#),
+        _ => Reply::status(404, "not found"),
+    }).await;
+    let gbolt = Godbolt::builder().base_url(&server.url).build_uncached()?;
+
+    match gbolt.get_languages().await {
+        Err(GodboltError::RateLimited { retry_after }) => assert_eq!(retry_after, Some(std::time::Duration::from_secs(7))),
+        other => panic!("expected rate limiting, got {:?}", other),
+    }
+    match gbolt.get_formats().await {
+        Err(GodboltError::Http { status, body }) => {
+            assert_eq!(status, 503);
+            assert_eq!(body, "maintenance");
+        }
+        other => panic!("expected HTTP error, got {:?}", other),
+    }
+    match gbolt.get_compilers().await {
+        Err(GodboltError::Deserialize { snippet, .. }) => assert!(snippet.contains(r#""alias":null"#)),
+        other => panic!("expected deserialize error, got {:?}", other),
+    }
+
+    let bad = Godbolt::builder().base_url("not a url").build_uncached();
+    assert!(matches!(bad, Err(GodboltError::Config(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn retries_transient_failures() -> Result<(), Box<dyn Error>> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::{Compiler, GodboltError, RetryPolicy};
+    use crate::tests::server::{serve, Reply};
+
+    let calls = AtomicUsize::new(0);
+    let server = serve("", move |req| match req.path.as_str() {
+        "/api/compiler/g132/compile" => match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => Reply::status(503, "busy"),
+            1 => Reply::status(429, "slow down").with_header("Retry-After", "0"),
+            _ => Reply::json(r#"{"code":0,"stdout":[],"stderr":[]}"#),
+        },
+        _ => Reply::status(502, "bad gateway"),
+    }).await;
+
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+        ..RetryPolicy::default()
+    };
+    let gbolt = Godbolt::builder().base_url(&server.url).retry_policy(policy).build_uncached()?;
+    let compiler = Compiler { id: "g132".to_string(), name: "x86-64 gcc 13.2".to_string(), lang: "c++".to_string(), ..Default::default() };
+
+    let res = gbolt.send_request(&compiler, "int main() {}", RequestOptions::default()).await?;
+    assert_eq!(res.attempts, 3);
+
+    match gbolt.get_languages().await {
+        Err(e @ GodboltError::RetriesExhausted { .. }) => {
+            assert_eq!(e.attempts(), 3);
+            assert_eq!(e.status(), Some(502));
+        }
+        other => panic!("expected retries to run out, got {:?}", other),
+    }
+
+    let single = Godbolt::builder().base_url(&server.url).build_uncached()?;
+    assert!(matches!(single.get_formats().await, Err(GodboltError::Http { status: 502, .. })));
+
+    // Policies that would compute a negative or NaN delay are rejected, and never panic
+    for multiplier in [-2.0, f64::NAN, f64::INFINITY] {
+        let policy = RetryPolicy { multiplier, ..RetryPolicy::default() };
+        assert!(matches!(Godbolt::builder().retry_policy(policy.clone()).build_uncached(), Err(GodboltError::Config(_))));
+        assert!(policy.delay(2, &GodboltError::Http { status: 503, body: String::new() }) <= policy.max_backoff);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_limit_waits_for_budget() -> Result<(), Box<dyn Error>> {
+    use std::time::{Duration, Instant};
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |_| Reply::json("[]")).await;
+
+    let gbolt = Godbolt::builder()
+        .base_url(&server.url)
+        .rate_limit(20.0, 1)
+        .max_in_flight(2)
+        .build_uncached()?;
+
+    let start = Instant::now();
+    let (a, b, c, d, e) = tokio::join!(
+        gbolt.get_formats(),
+        gbolt.get_languages(),
+        gbolt.get_compilers(),
+        gbolt.get_compilers_for("c"),
+        gbolt.get_libraries_for("c"),
+    );
+    assert!(a.is_ok() && b.is_ok() && c.is_ok() && d.is_ok() && e.is_ok());
+    // One request goes out immediately, the other four wait 50ms each
+    assert!(start.elapsed() >= Duration::from_millis(180), "finished in {:?}", start.elapsed());
+    assert_eq!(server.requests().len(), 5);
+
+    assert!(Godbolt::builder().rate_limit(0.0, 1).build_uncached().is_err());
+
+    // A tiny rate waits rather than panicking on a wait too long for `Duration`
+    let limiter = crate::limiter::RateLimiter::new(1e-20, 1);
+    limiter.acquire().await;
+    assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn snapshot_startup_and_refresh() -> Result<(), Box<dyn Error>> {
+    use std::time::Duration;
+    use crate::GodboltSnapshot;
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |req| match req.path.split('?').next().unwrap() {
+        "/api/languages" => Reply::json(r#"[{"id":"c++","name":"C++","extensions":[".cpp"],"monaco":"cppp","defaultCompiler":"g132"},{"id":"c","name":"C","extensions":[".c"],"monaco":"nc","defaultCompiler":"cg132"}]"#),
+        "/api/compilers" => Reply::json(r#"[{"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":[]},{"id":"cg132","name":"x86-64 gcc 13.2","lang":"c","alias":[]}]"#),
+        "/api/libraries/c++" => Reply::json(r#"[{"id":"fmt","name":"{fmt}","url":null,"versions":[]}]"#),
+        _ => Reply::json("[]"),
+    }).await;
+
+    let path = std::env::temp_dir().join(format!("godbolt-rs-snapshot-{}.json", std::process::id()));
+    let snapshot = GodboltSnapshot {
+        created_at: 0,
+        base_url: server.url.clone(),
+        languages: serde_json::from_str(r#"[{"id":"c++","name":"C++","extensions":[".cpp"],"monaco":"cppp","defaultCompiler":"g132"}]"#)?,
+        compilers: serde_json::from_str(r#"[{"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":[]}]"#)?,
+        ..GodboltSnapshot::default()
+    };
+    snapshot.save(&path)?;
+
+    // Fresh enough: no network at all
+    let offline = Godbolt::builder().base_url(&server.url).build_from_snapshot(&path, Duration::from_secs(u64::MAX / 4)).await?;
+    assert!(!offline.is_refreshing());
+    assert_eq!(offline.resolve("c++").map(|c| c.id), Some("g132".to_string()));
+    assert!(server.requests().is_empty());
+
+    // Stale: served from disk right away, refreshed in the background
+    let mut gbolt = Godbolt::builder().base_url(&server.url).build_from_snapshot(&path, Duration::from_secs(60)).await?;
+    assert!(gbolt.find_language_by_id("c").is_none());
+    assert!(gbolt.finish_refresh().await?);
+    assert!(gbolt.find_language_by_id("c").is_some());
+    assert!(!GodboltSnapshot::load(&path)?.is_stale(Duration::from_secs(60)));
+
+    gbolt.load_libraries("c++").await?;
+    gbolt.save_snapshot(&path)?;
+    let reloaded = GodboltSnapshot::load(&path)?;
+    assert_eq!(reloaded.libraries["c++"][0].id, "fmt");
+    assert_eq!(reloaded.compilers.len(), 2);
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Builds an instance whose cache holds the given languages and compilers, without any network
+fn offline(languages : &str, compilers : &str) -> Godbolt {
+    let mut gbolt = Godbolt::builder().build_uncached().unwrap();
+    gbolt.apply_snapshot(crate::GodboltSnapshot {
+        languages: serde_json::from_str(languages).unwrap(),
+        compilers: serde_json::from_str(compilers).unwrap(),
+        ..Default::default()
+    });
+    gbolt
+}
+
+const LANGUAGES : &str = r#"[
+    {"id":"c++","name":"C++","extensions":[".cpp",".cxx",".h"],"monaco":"cppp","defaultCompiler":"g132"},
+    {"id":"c","name":"C","extensions":[".c",".h"],"monaco":"nc","defaultCompiler":"cg132"},
+    {"id":"rust","name":"Rust","extensions":[".rs"],"monaco":"rust","defaultCompiler":"r1740"}
+]"#;
+
+const COMPILERS : &str = r#"[
+    {"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":["gsnapshot132"],"instructionSet":"amd64","compilerType":"","semver":"13.2",
+     "version":"g++ (Compiler-Explorer-Build-gcc--binutils-2.40) 13.2.0","group":"gcc86","groupName":"GCC x86-64","supportsExecute":true,
+     "supportsBinary":true,"supportsBinaryObject":true,"supportsIntel":true,"supportsDemangle":true,"libsArr":[],"disabledFilters":[],
+     "tools":{"llvm-mcatrunk":{"id":"llvm-mcatrunk","name":"llvm-mca (trunk)","type":"postcompilation","allowStdin":false},
+              "clangtidytrunk":{"id":"clangtidytrunk","name":"clang-tidy (trunk)","type":"independent","allowStdin":false}},
+     "possibleOverrides":[],"exe":"/opt/compiler-explorer/gcc-13.2.0/bin/g++"},
+    {"id":"clang1000","name":"x86-64 clang 10.0.0","lang":"c++","alias":[],"compilerType":"clang","semver":"10.0.0","instructionSet":"amd64"},
+    {"id":"cg132","name":"x86-64 gcc 13.2","lang":"c","alias":[]},
+    {"id":"r1740","name":"rustc 1.74.0","lang":"rust","alias":["rustc"]}
+]"#;
+
+#[test]
+fn indexed_lookup() {
+    let gbolt = offline(LANGUAGES, COMPILERS);
+    assert_eq!(gbolt.find_compiler_by_id("G132").map(|c| c.name.as_str()), Some("x86-64 gcc 13.2"));
+    assert_eq!(gbolt.find_compiler_by_id("gsnapshot132").map(|c| c.id.as_str()), Some("g132"));
+    assert_eq!(gbolt.find_language_by_id("cpp").map(|l| l.id.as_str()), Some("c++"));
+    assert_eq!(gbolt.find_language_by_id(".RS").map(|l| l.id.as_str()), Some("rust"));
+    assert_eq!(gbolt.find_language_by_id("Rust").map(|l| l.id.as_str()), Some("rust"));
+    // Ids win over extensions shared between languages
+    assert_eq!(gbolt.find_language_by_id("c").map(|l| l.id.as_str()), Some("c"));
+    assert_eq!(gbolt.resolve("rustc").map(|c| c.id), Some("r1740".to_string()));
+    assert_eq!(gbolt.resolve(".cxx").map(|c| c.id), Some("g132".to_string()));
+    assert!(gbolt.resolve("gcc1320").is_none());
+
+    // Editing the cache without reindexing never returns the wrong entry
+    let mut gbolt = gbolt;
+    gbolt.cache.retain(|e| e.language.id != "c++");
+    assert!(gbolt.find_compiler_by_id("g132").is_none());
+    // Its indexed position now holds rustc
+    assert_eq!(gbolt.find_compiler_by_id("cg132").map(|c| c.id.as_str()), Some("cg132"));
+    assert_eq!(gbolt.find_compiler_by_id("rustc").map(|c| c.id.as_str()), Some("r1740"));
+    assert_eq!(gbolt.find_language_by_id(".rs").map(|l| l.id.as_str()), Some("rust"));
+    assert_eq!(gbolt.find_language_by_id("h").map(|l| l.id.as_str()), Some("c"));
+    let mut added = gbolt.cache[0].compilers[0].clone();
+    added.id = "cclang17".to_string();
+    gbolt.cache[0].compilers.push(added);
+    assert_eq!(gbolt.find_compiler_by_id("CClang17").map(|c| c.id.as_str()), Some("cclang17"));
+}
+
+#[test]
+fn suggestions_for_unknown_compilers() {
+    let gbolt = offline(LANGUAGES, COMPILERS);
+
+    let suggestions = gbolt.resolve_with_suggestions("gcc1320", None, 3).unwrap_err();
+    assert!(!suggestions.is_empty());
+    assert!(["g132", "cg132"].contains(&suggestions[0].id.as_str()), "{:?}", suggestions);
+    assert!(suggestions.iter().all(|s| s.id != "r1740"));
+
+    let scoped = gbolt.suggest("gcc1320", Some("cpp"), 3);
+    assert_eq!(scoped[0].id, "g132");
+    assert!(scoped.iter().all(|s| s.lang == "c++"));
+    assert_eq!(scoped[0].to_string(), "g132 (x86-64 gcc 13.2)");
+
+    assert_eq!(gbolt.suggest("clang100", None, 1)[0].id, "clang1000");
+    assert!(gbolt.resolve_with_suggestions("rustc", None, 3).is_ok());
+}
+
+#[test]
+fn compiler_metadata() -> Result<(), Box<dyn Error>> {
+    let gbolt = offline(LANGUAGES, COMPILERS);
+    let gcc = gbolt.find_compiler_by_id("g132").unwrap();
+    assert_eq!(gcc.instruction_set.as_deref(), Some("amd64"));
+    assert_eq!(gcc.semver.as_deref(), Some("13.2"));
+    assert_eq!(gcc.group_name.as_deref(), Some("GCC x86-64"));
+    assert_eq!(gcc.supports_execute, Some(true));
+    assert_eq!(gcc.tools["llvm-mcatrunk"].tool_type.as_deref(), Some("postcompilation"));
+
+    let rustc = gbolt.find_compiler_by_id("r1740").unwrap();
+    assert_eq!(rustc.supports_execute, None);
+    assert!(rustc.tools.is_empty());
+
+    // Metadata survives a snapshot round trip
+    let json = serde_json::to_string(&gbolt.snapshot())?;
+    let snapshot : crate::GodboltSnapshot = serde_json::from_str(&json)?;
+    let gcc = snapshot.compilers.iter().find(|c| c.id == "g132").unwrap();
+    assert_eq!(gcc.supports_binary, Some(true));
+    assert_eq!(gcc.tools.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn validation_before_sending() -> Result<(), Box<dyn Error>> {
+    use crate::{CompilationRequest, GodboltError, ValidationIssue};
+    let gbolt = offline(LANGUAGES, r#"[
+        {"id":"armg132","name":"ARM gcc 13.2","lang":"c++","alias":[],"supportsExecute":false,"supportsBinary":true,
+         "supportsIntel":false,"supportsDemangle":true,"disabledFilters":["labels","debugCalls"],"supportsBinaryObject":false}
+    ]"#);
+    let arm = gbolt.find_compiler_by_id("armg132").unwrap().clone();
+
+    let options = RequestOptions {
+        compiler_options: CompilerOptions { executor_request: true, ..Default::default() },
+        filters: CompilationFilters { intel: Some(true), labels: Some(true), demangle: Some(true), ..Default::default() },
+        ..Default::default()
+    };
+    let report = gbolt.validate(&CompilationRequest::new(&arm, "int main() {}", options.clone()));
+    assert_eq!(report.errors, vec![ValidationIssue::ExecutionUnsupported("armg132".to_string())]);
+    assert_eq!(report.warnings, vec![
+        ValidationIssue::IntelUnsupported("armg132".to_string()),
+        ValidationIssue::FilterDisabled { compiler: "armg132".to_string(), filter: "labels".to_string() },
+    ]);
+
+    // Rejected before any connection is attempted; the base URL is never contacted
+    match gbolt.send_request(&arm, "int main() {}", options).await {
+        Err(GodboltError::Validation(issues)) => assert_eq!(issues.len(), 1),
+        other => panic!("expected validation to fail, got {:?}", other),
+    }
+    // A bare compiler is checked against the cached metadata for its id
+    let bare = crate::Compiler { id: "armg132".to_string(), ..Default::default() };
+    let options = RequestOptions {
+        filters: CompilationFilters { binary_object: Some(true), debug_calls: Some(true), ..Default::default() },
+        ..Default::default()
+    };
+    let report = gbolt.validate(&CompilationRequest::new(&bare, "int main() {}", options.clone()));
+    assert_eq!(report.errors, vec![ValidationIssue::BinaryObjectUnsupported("armg132".to_string())]);
+    assert_eq!(report.warnings, vec![ValidationIssue::FilterDisabled { compiler: "armg132".to_string(), filter: "debugCalls".to_string() }]);
+    match gbolt.send_request(&bare, "int main() {}", options).await {
+        Err(GodboltError::Validation(issues)) => assert_eq!(issues, vec![ValidationIssue::BinaryObjectUnsupported("armg132".to_string())]),
+        other => panic!("expected validation to fail, got {:?}", other),
+    }
+
+    let unknown = crate::Compiler { id: "nope".to_string(), ..Default::default() };
+    let report = gbolt.validate(&CompilationRequest::new(&unknown, "", RequestOptions::default()));
+    assert_eq!(report.errors, vec![ValidationIssue::UnknownCompiler("nope".to_string())]);
+    Ok(())
+}
+
+#[test]
+fn compiler_versions() {
+    use crate::Version;
+    let gbolt = offline(LANGUAGES, r#"[
+        {"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":[],"instructionSet":"amd64","compilerType":"","semver":"13.2"},
+        {"id":"g122","name":"x86-64 gcc 12.2","lang":"c++","alias":[],"semver":"12.2"},
+        {"id":"arm64g132","name":"ARM64 gcc 13.2","lang":"c++","alias":[],"instructionSet":"aarch64","semver":"13.2"},
+        {"id":"rv64-gcc1320","name":"RISC-V (64-bits) gcc 13.2.0","lang":"c++","alias":[]},
+        {"id":"gsnapshot","name":"x86-64 gcc (trunk)","lang":"c++","alias":[],"semver":"(trunk)"},
+        {"id":"clang1000","name":"x86-64 clang 10.0.0","lang":"c++","alias":[],"compilerType":"clang","semver":"10.0.0"},
+        {"id":"clang1701","name":"x86-64 clang 17.0.1","lang":"c++","alias":[],"compilerType":"clang","semver":"17.0.1"},
+        {"id":"armv8-clang1500","name":"armv8-a clang 15.0.0","lang":"c++","alias":[],"compilerType":"clang","semver":"15.0.0"},
+        {"id":"vcpp_v19_latest_x64","name":"x64 msvc v19.latest","lang":"c++","alias":[],"compilerType":"win32-vc","semver":"v19.latest"},
+        {"id":"r1740","name":"rustc 1.74.0","lang":"rust","alias":[],"semver":"1.74.0"},
+        {"id":"nightly","name":"rustc nightly","lang":"rust","alias":[],"semver":"nightly"}
+    ]"#);
+
+    let info = gbolt.find_compiler_by_id("arm64g132").unwrap().version_info();
+    assert_eq!(info.family, "gcc");
+    assert_eq!(info.version, Some(Version::new(13, 2, 0)));
+    assert_eq!(info.arch.as_deref(), Some("aarch64"));
+    assert!(!info.trunk);
+
+    // Without semver the version follows the family, not the first number in the name
+    let riscv = gbolt.find_compiler_by_id("rv64-gcc1320").unwrap().version_info();
+    assert_eq!((riscv.family.as_str(), riscv.version, riscv.arch.as_deref()), ("gcc", Some(Version::new(13, 2, 0)), Some("riscv64")));
+    let riscv = crate::Compiler { name: "RISC-V (64-bits) gcc 14.2.0".to_string(), ..Default::default() }.version_info();
+    assert_eq!(riscv.version, Some(Version::new(14, 2, 0)));
+
+    let msvc = gbolt.find_compiler_by_id("vcpp_v19_latest_x64").unwrap().version_info();
+    assert_eq!((msvc.family.as_str(), msvc.version, msvc.arch.as_deref()), ("msvc", Some(Version::new(19, 0, 0)), Some("x86_64")));
+
+    assert_eq!(gbolt.latest("gcc", "c++").map(|c| c.id.as_str()), Some("g132"));
+    assert_eq!(gbolt.latest("G++", "cpp").map(|c| c.id.as_str()), Some("g132"));
+    assert_eq!(gbolt.trunk("gcc", "c++").map(|c| c.id.as_str()), Some("gsnapshot"));
+    assert_eq!(gbolt.trunk("rustc", "rust").map(|c| c.id.as_str()), Some("nightly"));
+    assert_eq!(gbolt.latest("rustc", "rust").map(|c| c.id.as_str()), Some("r1740"));
+    assert!(gbolt.latest("icc", "c++").is_none());
+
+    let modern : Vec<&str> = gbolt.compilers_in_range("clang", Version::new(15, 0, 0)..)
+        .iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(modern, vec!["armv8-clang1500", "clang1701"]);
+    assert_eq!(gbolt.compilers_in_range("gcc", ..Version::new(13, 0, 0)).len(), 1);
+    assert_eq!(Version::parse("1.74.0-nightly"), Some(Version::new(1, 74, 0)));
+}
+
+#[test]
+fn compiler_queries() {
+    use crate::{CompilerQuery, Version};
+    let gbolt = offline(LANGUAGES, r#"[
+        {"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":[],"instructionSet":"amd64","semver":"13.2","supportsExecute":true,"supportsBinary":true},
+        {"id":"g122","name":"x86-64 gcc 12.2","lang":"c++","alias":[],"instructionSet":"amd64","semver":"12.2","supportsExecute":true},
+        {"id":"arm64g132","name":"ARM64 gcc 13.2","lang":"c++","alias":[],"instructionSet":"aarch64","semver":"13.2","supportsExecute":false},
+        {"id":"gsnapshot","name":"x86-64 gcc (trunk)","lang":"c++","alias":[],"instructionSet":"amd64","semver":"(trunk)"},
+        {"id":"clang1701","name":"x86-64 clang 17.0.1","lang":"c++","alias":[],"compilerType":"clang","semver":"17.0.1","supportsExecute":true},
+        {"id":"cg132","name":"x86-64 gcc 13.2","lang":"c","alias":[],"semver":"13.2","supportsExecute":true},
+        {"id":"r1740","name":"rustc 1.74.0","lang":"rust","alias":[],"semver":"1.74.0","supportsExecute":true}
+    ]"#);
+    let ids = |q : &CompilerQuery| gbolt.query(q).items.iter().map(|c| c.id.as_str()).collect::<Vec<_>>();
+
+    assert_eq!(ids(&CompilerQuery::new().language("cpp").family("gcc")), vec!["gsnapshot", "arm64g132", "g132", "g122"]);
+    assert_eq!(ids(&CompilerQuery::new().family("gcc").arch("x64").versions(Version::new(13, 0, 0)..)), vec!["cg132", "g132"]);
+    assert_eq!(ids(&CompilerQuery::new().language("c++").can_execute(true).supports_binary(true)), vec!["g132"]);
+    assert_eq!(ids(&CompilerQuery::new().text("ARM64 13")), vec!["arm64g132"]);
+    assert!(ids(&CompilerQuery::new().language("cobol")).is_empty());
+
+    let page = gbolt.query(&CompilerQuery::new().can_execute(true).page(1, 2));
+    assert_eq!(page.total, 5);
+    assert_eq!(page.pages, 3);
+    assert_eq!(page.items.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), vec!["g132", "g122"]);
+}
+
+/// Library list of a language as `/api/libraries/<lang>` returns it
+const LIBRARIES : &str = r#"[
+    {"id":"fmt","name":"{fmt}","url":"https://github.com/fmtlib/fmt","versions":[
+        {"version":"10.1.0","staticliblink":["fmtd"],"description":null,"alias":[],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"1010"},
+        {"version":"9.1.0","staticliblink":["fmtd"],"description":null,"alias":[],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"910"},
+        {"version":"trunk","staticliblink":["fmtd"],"description":null,"alias":[],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"trunk"}]},
+    {"id":"boost","name":"Boost","url":null,"versions":[
+        {"version":"1.83.0","staticliblink":[],"description":null,"alias":["1.83"],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"183"},
+        {"version":"1.82.0","staticliblink":[],"description":null,"alias":[],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"182"}]},
+    {"id":"spdlog","name":"spdlog","url":null,"versions":[
+        {"version":"1.12.0","staticliblink":[],"description":null,"alias":[],"dependencies":["fmt"],"path":[],"libpath":[],"options":[],"id":"1120"}]},
+    {"id":"rangesv3","name":"range-v3","url":null,"versions":[
+        {"version":"0.12.0","staticliblink":[],"description":null,"alias":["0.12"],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"0120"},
+        {"version":"0.11.0","staticliblink":[],"description":null,"alias":[],"dependencies":[],"path":[],"libpath":[],"options":[],"id":"0110"}]}
+]"#;
+
+/// Like [`offline`], with [`LIBRARIES`] loaded for C++
+fn offline_with_libraries(compilers : &str) -> Godbolt {
+    let mut snapshot = offline(LANGUAGES, compilers).snapshot();
+    snapshot.libraries.insert("c++".to_string(), serde_json::from_str(LIBRARIES).unwrap());
+    let mut gbolt = Godbolt::builder().build_uncached().unwrap();
+    gbolt.apply_snapshot(snapshot);
+    gbolt
+}
+
+#[test]
+fn libraries_in_requests() -> Result<(), Box<dyn Error>> {
+    use base64::Engine;
+    use crate::{CompilationRequest, LibraryRef, ValidationIssue};
+    let gbolt = offline_with_libraries(r#"[
+        {"id":"g132","name":"x86-64 gcc 13.2","lang":"c++","alias":[]},
+        {"id":"nvcc","name":"NVCC 12.3","lang":"c++","alias":[],"libsArr":["fmt"]}
+    ]"#);
+    let gcc = gbolt.find_compiler_by_id("g132").unwrap();
+    let nvcc = gbolt.find_compiler_by_id("nvcc").unwrap();
+
+    let options = RequestOptions {
+        libraries: vec![LibraryRef::new("fmt", "1010"), LibraryRef::new("boost", "183")],
+        ..Default::default()
+    };
+    let body = serde_json::to_value(CompilationRequest::new(gcc, "", options.clone()))?;
+    assert_eq!(body["options"]["libraries"][1], serde_json::json!({"id": "boost", "version": "183"}));
+    assert!(gbolt.validate(&CompilationRequest::new(gcc, "", options.clone())).is_ok());
+
+    let report = gbolt.validate(&CompilationRequest::new(nvcc, "", options.clone()));
+    assert_eq!(report.errors, vec![ValidationIssue::LibraryUnsupported { compiler: "nvcc".to_string(), library: "boost".to_string() }]);
+
+    let bad = RequestOptions {
+        libraries: vec![LibraryRef::new("fmt", "10.1"), LibraryRef::new("abseil", "trunk")],
+        ..Default::default()
+    };
+    let report = gbolt.validate(&CompilationRequest::new(gcc, "", bad));
+    assert_eq!(report.errors, vec![
+        ValidationIssue::UnknownLibraryVersion { library: "fmt".to_string(), version: "10.1".to_string() },
+        ValidationIssue::UnknownLibrary("abseil".to_string()),
+    ]);
+
+    let state = Godbolt::get_base64(gcc, "int main() {}", options)?;
+    let json : serde_json::Value = serde_json::from_slice(&base64::engine::general_purpose::STANDARD.decode(state)?)?;
+    let libs = serde_json::json!([{"name": "fmt", "ver": "1010"}, {"name": "boost", "ver": "183"}]);
+    assert_eq!(json["sessions"][0]["compilers"][0]["libs"], libs);
+    assert_eq!(json["sessions"][0]["executors"][0]["compiler"]["libs"], libs);
+    Ok(())
+}
+
+#[test]
+fn library_specs() {
+    use crate::{LibraryError, LibraryRef, LibrarySpec};
+    let gbolt = offline_with_libraries(COMPILERS);
+
+    let spec : LibrarySpec = "range-v3@0.12".parse().unwrap();
+    assert_eq!((spec.name.as_str(), spec.version.as_deref()), ("range-v3", Some("0.12")));
+    assert_eq!(spec.to_string(), "range-v3@0.12");
+    assert!(LibrarySpec::parse("fmt@").is_err());
+
+    let refs = gbolt.resolve_libraries("cpp", &["fmt@trunk", "boost", "range-v3@0.12", "Boost"]).unwrap();
+    assert_eq!(refs, vec![LibraryRef::new("fmt", "trunk"), LibraryRef::new("boost", "183"), LibraryRef::new("rangesv3", "0120")]);
+
+    // Dependencies are added, and reuse an explicitly picked version
+    let refs = gbolt.resolve_libraries("c++", &["spdlog"]).unwrap();
+    assert_eq!(refs, vec![LibraryRef::new("spdlog", "1120"), LibraryRef::new("fmt", "1010")]);
+    let refs = gbolt.resolve_libraries("c++", &["fmt@9.1", "spdlog"]).unwrap();
+    assert_eq!(refs, vec![LibraryRef::new("fmt", "910"), LibraryRef::new("spdlog", "1120")]);
+
+    let errors = gbolt.resolve_libraries("c++", &["fmt@10.1", "fmt@trunk", "abseil", "boost@0.1", "x y"]).unwrap_err();
+    assert_eq!(errors, vec![
+        LibraryError::InvalidSpec("x y".to_string()),
+        LibraryError::Conflict { library: "fmt".to_string(), first: "1010".to_string(), second: "trunk".to_string() },
+        LibraryError::UnknownLibrary("abseil".to_string()),
+        LibraryError::UnknownVersion { library: "boost".to_string(), version: "0.1".to_string() },
+    ]);
+    assert_eq!(gbolt.resolve_libraries("rust", &["serde"]).unwrap_err(), vec![LibraryError::NotLoaded("rust".to_string())]);
+}
+
+#[tokio::test]
+async fn multi_file_requests() -> Result<(), Box<dyn Error>> {
+    use base64::Engine;
+    use crate::CompilationRequest;
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |_| Reply::json(r#"{"code":0,"stdout":[],"stderr":[]}"#)).await;
+    let mut gbolt = offline(LANGUAGES, COMPILERS);
+    gbolt.base_url = server.url.clone();
+    let c = gbolt.find_compiler_by_id("g132").unwrap();
+
+    let req = CompilationRequest::new(c, "#include \"square.h\"\nint main() { return square(2); }", RequestOptions::default())
+        .with_file("square.h", "int square(int);")
+        .with_file("square.cpp", "int square(int x) { return x * x; }");
+    assert_eq!(req.main_filename(), "example.cpp");
+    assert_eq!(req.files().len(), 2);
+    let res = gbolt.send_compilation(&req).await?;
+    assert_eq!(res.code, 0);
+
+    let body : serde_json::Value = serde_json::from_str(&server.requests()[0].body)?;
+    assert_eq!(server.requests()[0].path, "/api/compiler/g132/compile");
+    assert_eq!(body["files"], serde_json::json!([
+        {"filename": "square.h", "contents": "int square(int);"},
+        {"filename": "square.cpp", "contents": "int square(int x) { return x * x; }"},
+    ]));
+
+    // Single-file requests are sent and linked exactly as before
+    let single = serde_json::to_value(CompilationRequest::new(c, "", RequestOptions::default()))?;
+    assert!(single.get("files").is_none());
+    let state = Godbolt::get_base64(c, "", RequestOptions::default())?;
+    let json : serde_json::Value = serde_json::from_slice(&base64::engine::general_purpose::STANDARD.decode(state)?)?;
+    assert_eq!(json["trees"], serde_json::json!([]));
+    assert_eq!(json["sessions"][0]["compilers"][0]["id"], "g132");
+
+    let state = req.with_main_filename("main.cpp").client_state().to_base64()?;
+    let json : serde_json::Value = serde_json::from_slice(&base64::engine::general_purpose::STANDARD.decode(state)?)?;
+    let tree = &json["trees"][0];
+    assert_eq!(tree["compilers"][0]["id"], "g132");
+    assert_eq!(tree["newFileId"], 4);
+    let files : Vec<(&str, bool)> = tree["files"].as_array().unwrap().iter()
+        .map(|f| (f["filename"].as_str().unwrap(), f["isMainSource"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(files, vec![("main.cpp", true), ("square.h", false), ("square.cpp", false)]);
+    assert_eq!(json["sessions"].as_array().unwrap().len(), 3);
+    assert_eq!(json["sessions"][2]["filename"], "square.cpp");
+    assert_eq!(json["sessions"][2]["compilers"], serde_json::json!([]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cmake_projects() -> Result<(), Box<dyn Error>> {
+    use crate::{CompilationRequest, GodboltError, SourceFile, ValidationIssue, CMAKELISTS};
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |_| Reply::json(r#"{
+        "code": 0,
+        "buildsteps": [
+            {"step": "cmake", "code": 0, "stdout": [{"text": "-- Configuring done"}], "stderr": []},
+            {"step": "build", "code": 0, "stdout": [{"text": "[100%] Built target app"}], "stderr": [{"text": "warning: unused"}]}
+        ],
+        "result": {"code": 0, "asm": [{"text": "main:"}]},
+        "didExecute": true,
+        "execResult": {"code": 3, "didExecute": true, "stdout": [{"text": "hello"}], "stderr": []}
+    }"#)).await;
+    let mut gbolt = offline(LANGUAGES, COMPILERS);
+    gbolt.base_url = server.url.clone();
+    let c = gbolt.find_compiler_by_id("g132").unwrap();
+
+    let files = [
+        SourceFile::new(CMAKELISTS, "project(app)\nadd_executable(app main.cpp)"),
+        SourceFile::new("main.cpp", "int main() { return 3; }"),
+    ];
+    let res = gbolt.send_cmake_request(c, &files, "-DCMAKE_BUILD_TYPE=Release", RequestOptions::default()).await?;
+    assert!(res.succeeded());
+    assert_eq!(res.configure_step().unwrap().stdout[0].text, "-- Configuring done");
+    assert_eq!(res.build_step().unwrap().stderr[0].text, "warning: unused");
+    assert_eq!(res.result.as_ref().unwrap().asm.as_ref().unwrap()[0].text.as_deref(), Some("main:"));
+    let exec = res.exec_result.as_ref().unwrap();
+    assert_eq!((exec.code, exec.stdout[0].text.as_str()), (3, "hello"));
+
+    let recorded = &server.requests()[0];
+    assert_eq!(recorded.path, "/api/compiler/g132/cmake");
+    let body : serde_json::Value = serde_json::from_str(&recorded.body)?;
+    assert_eq!(body["source"], "project(app)\nadd_executable(app main.cpp)");
+    assert_eq!(body["options"]["compilerOptions"]["cmakeArgs"], "-DCMAKE_BUILD_TYPE=Release");
+    assert_eq!(body["files"], serde_json::json!([{"filename": "main.cpp", "contents": "int main() { return 3; }"}]));
+
+    let req = CompilationRequest::cmake(c, &files, "", RequestOptions::default());
+    let tree = &req.client_state().trees[0];
+    assert!(tree.is_cmake_project);
+    assert_eq!(tree.files[0].filename, CMAKELISTS);
+    assert!(tree.files[0].is_main_source);
+
+    let missing = gbolt.send_cmake_request(c, &files[1..], "", RequestOptions::default()).await;
+    match missing {
+        Err(GodboltError::Validation(errors)) => assert_eq!(errors, vec![ValidationIssue::MissingFile(CMAKELISTS.to_string())]),
+        other => panic!("expected a validation error, got {:?}", other.map(|r| r.code)),
+    }
+    assert_eq!(server.requests().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn compiler_tools() -> Result<(), Box<dyn Error>> {
+    use crate::{CompilationRequest, ToolRequest, ValidationIssue};
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |_| Reply::json(r#"{
+        "code": 0, "stdout": [], "stderr": [],
+        "tools": [{
+            "id": "clangtidytrunk", "name": "clang-tidy (trunk)", "code": 1,
+            "stdout": [
+                {"text": "<source>:3:5: warning: use nullptr [modernize-use-nullptr]",
+                 "tag": {"line": 3, "column": 5, "text": "use nullptr [modernize-use-nullptr]", "severity": 4, "file": "example.cpp"}},
+                {"text": "1 warning generated."}
+            ],
+            "stderr": [{"text": "Error while processing /app/example.cpp."}]
+        }]
+    }"#)).await;
+    let mut gbolt = offline(LANGUAGES, COMPILERS);
+    gbolt.base_url = server.url.clone();
+    let gcc = gbolt.find_compiler_by_id("g132").unwrap();
+
+    let mut available : Vec<&str> = gbolt.tools_for("gsnapshot132").iter().map(|t| t.id.as_str()).collect();
+    available.sort();
+    assert_eq!(available, vec!["clangtidytrunk", "llvm-mcatrunk"]);
+    assert_eq!(gcc.tool("llvm-mcatrunk").and_then(|t| t.name.as_deref()), Some("llvm-mca (trunk)"));
+    assert!(gbolt.tools_for("r1740").is_empty());
+
+    let options = RequestOptions {
+        tools: vec![ToolRequest::new("clangtidytrunk", "-checks=modernize-*")],
+        ..Default::default()
+    };
+    let res = gbolt.send_request(gcc, "int* p = 0;", options).await?;
+    let tidy = &res.tools[0];
+    assert_eq!((tidy.id.as_str(), tidy.name.as_deref(), tidy.code), ("clangtidytrunk", Some("clang-tidy (trunk)"), 1));
+    assert!(!tidy.succeeded());
+    assert_eq!(tidy.stdout_text(), "<source>:3:5: warning: use nullptr [modernize-use-nullptr]\n1 warning generated.");
+    let diagnostics = tidy.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column, diagnostics[0].severity), (3, 5, Some(4)));
+    assert_eq!(diagnostics[0].file.as_deref(), Some("example.cpp"));
+
+    let body : serde_json::Value = serde_json::from_str(&server.requests()[0].body)?;
+    assert_eq!(body["options"]["tools"], serde_json::json!([{"id": "clangtidytrunk", "args": "-checks=modernize-*"}]));
+
+    let options = RequestOptions {
+        tools: vec![ToolRequest::new("pahole", ""), ToolRequest::new("llvm-mcatrunk", "").with_stdin("ignored")],
+        ..Default::default()
+    };
+    let report = gbolt.validate(&CompilationRequest::new(gcc, "", options.clone()));
+    assert_eq!(report.errors, vec![ValidationIssue::ToolUnavailable { compiler: "g132".to_string(), tool: "pahole".to_string() }]);
+    assert_eq!(report.warnings, vec![ValidationIssue::ToolStdinIgnored("llvm-mcatrunk".to_string())]);
+    // Without tool metadata, any tool is let through
+    let rustc = gbolt.find_compiler_by_id("r1740").unwrap();
+    assert!(gbolt.validate(&CompilationRequest::new(rustc, "", options)).errors.is_empty());
+    Ok(())
+}
+
+/// llvm-mca output for a three instruction block, as the llvm-mca tool prints it
+const MCA_OUTPUT : &str = "Iterations:        100
+Instructions:      300
+Total Cycles:      104
+Total uOps:        400
+
+Dispatch Width:    6
+uOps Per Cycle:    3.85
+IPC:               2.88
+Block RThroughput: 1.0
+
+
+Instruction Info:
+[1]: #uOps
+[2]: Latency
+[3]: RThroughput
+[4]: MayLoad
+[5]: MayStore
+[6]: HasSideEffects (U)
+
+[1]    [2]    [3]    [4]    [5]    [6]    Instructions:
+ 1      1     0.25                        leal\t(%rdi,%rdi), %eax
+ 2      6     0.50    *                   addl\t(%rsi), %eax
+ 1      1     1.00                  U     retq
+
+
+Resources:
+[0]   - SKLDivider
+[1]   - SKLPort0
+[2]   - SKLPort1
+[3]   - SKLPort2
+[4]   - SKLPort6
+
+
+Resource pressure per iteration:
+[0]    [1]    [2]    [3]    [4]
+ -     0.50   0.50   0.50   1.00
+
+Resource pressure by instruction:
+[0]    [1]    [2]    [3]    [4]    Instructions:
+ -     0.25   0.25    -     0.50   leal\t(%rdi,%rdi), %eax
+ -     0.25   0.25   0.50    -     addl\t(%rsi), %eax
+ -      -      -      -     0.50   retq
+";
+
+#[test]
+fn llvm_mca_reports() -> Result<(), Box<dyn Error>> {
+    use crate::{McaReport, ToolResult};
+    let report = McaReport::parse(MCA_OUTPUT).expect("summary should parse");
+    assert_eq!((report.iterations, report.instructions, report.total_cycles, report.total_uops), (100, 300, 104, 400));
+    assert_eq!(report.dispatch_width, 6);
+    assert_eq!((report.uops_per_cycle, report.ipc, report.block_rthroughput), (3.85, 2.88, 1.0));
+    assert_eq!(report.cycles_per_iteration(), 1.04);
+
+    let add = &report.instruction_info[1];
+    assert_eq!((add.uops, add.latency, add.rthroughput), (2, 6, 0.5));
+    assert!(add.may_load && !add.may_store && !add.has_side_effects);
+    assert_eq!(add.text, "addl (%rsi), %eax");
+    assert!(report.instruction_info[2].has_side_effects);
+
+    assert_eq!(report.resources[1], ("1".to_string(), "SKLPort0".to_string()));
+    assert_eq!(report.resource_pressure, vec![0.0, 0.5, 0.5, 0.5, 1.0]);
+    assert_eq!(report.pressure_on("SKLPort6"), Some(1.0));
+    assert_eq!(report.pressure_by_instruction[1].pressure, vec![0.0, 0.25, 0.25, 0.5, 0.0]);
+    assert_eq!(report.pressure_by_instruction[2].text, "retq");
+
+    // The tool's output arrives one line per entry
+    let tool : ToolResult = serde_json::from_value(serde_json::json!({
+        "id": "llvm-mcatrunk", "code": 0,
+        "stdout": MCA_OUTPUT.lines().map(|l| serde_json::json!({"text": l})).collect::<Vec<_>>(),
+    }))?;
+    assert_eq!(McaReport::from_tool(&tool), Some(report.clone()));
+
+    let regions = format!("[0] Code Region - hot\n\n{}\n[1] Code Region\n\n{}", MCA_OUTPUT, MCA_OUTPUT.replace("Total Cycles:      104", "Total Cycles:      210"));
+    let regions = McaReport::parse_regions(&regions);
+    assert_eq!(regions.len(), 2);
+    assert_eq!(regions[0].region.as_deref(), Some("hot"));
+    assert_eq!((regions[1].region.as_deref(), regions[1].total_cycles), (None, 210));
+
+    // Reports serialize for storing and comparing across compilers
+    let json = serde_json::to_value(&report)?;
+    assert_eq!(json["instruction_info"][0]["latency"], 1);
+    assert!(McaReport::parse("error: no assembly instructions found.").is_none());
+    Ok(())
+}
+
+/// Client state as the Compiler Explorer UI writes it, with a conformance view and a tree
+const CLIENT_STATE : &str = r#"{
+    "sessions": [{
+        "id": 1, "language": "c++", "source": "int square(int x) { return x * x; }",
+        "conformanceview": {"libs": [{"name": "fmt", "ver": "1010"}], "compilers": [{"id": "clang1000", "options": "-std=c++20", "libs": [], "specialoutputs": [], "tools": []}]},
+        "compilers": [{
+            "_internalid": 1, "id": "g132", "options": "-O2",
+            "filters": {"binary": false, "binaryObject": false, "commentOnly": true, "demangle": true, "directives": true,
+                        "execute": false, "intel": true, "labels": true, "libraryCode": false, "trim": false, "debugCalls": false,
+                        "verboseDemangling": true},
+            "libs": [{"name": "fmt", "ver": "1010"}], "specialoutputs": ["ir", "opt"],
+            "tools": [{"id": "llvm-mcatrunk", "args": "-timeline"}],
+            "overrides": [{"name": "stdver", "value": "c++20"}]
+        }],
+        "executors": [{
+            "compilerVisible": false, "compilerOutputVisible": false, "arguments": "1 2", "argumentsVisible": true,
+            "stdin": "", "stdinVisible": false, "wrap": true,
+            "compiler": {"id": "g132", "options": "-O2", "libs": [], "specialoutputs": [], "tools": []}
+        }]
+    }, {
+        "id": 2, "language": "c++", "source": "int square(int);", "conformanceview": false,
+        "compilers": [], "executors": [], "filename": "square.h"
+    }],
+    "trees": [{
+        "id": 1, "cmakeArgs": "", "customOutputFilename": "", "isCMakeProject": false, "compilerLanguageId": "c++",
+        "files": [{"fileId": 1, "isIncluded": true, "isOpen": true, "isMainSource": true, "filename": "example.cpp",
+                   "content": "", "editorId": 2, "langId": "c++", "isDirty": false}],
+        "newFileId": 2, "compilers": [], "executors": []
+    }],
+    "layoutVersion": 4
+}"#;
+
+#[test]
+fn client_state_round_trip() -> Result<(), Box<dyn Error>> {
+    use crate::ClientState;
+    let original : serde_json::Value = serde_json::from_str(CLIENT_STATE)?;
+    let state : ClientState = serde_json::from_value(original.clone())?;
+
+    let session = &state.sessions[0];
+    let gcc = &session.compilers[0];
+    assert_eq!((gcc.internal_id, gcc.id.as_str(), gcc.options.as_str()), (Some(1), "g132", "-O2"));
+    assert_eq!(gcc.filters.as_ref().and_then(|f| f.debug_calls), Some(false));
+    assert_eq!(gcc.filters.as_ref().map(|f| &f.extra["verboseDemangling"]), Some(&serde_json::Value::Bool(true)));
+    assert_eq!(gcc.specialoutputs, vec!["ir", "opt"]);
+    assert_eq!((gcc.tools[0].id.as_str(), gcc.tools[0].args.as_str()), ("llvm-mcatrunk", "-timeline"));
+    assert_eq!(gcc.extra["overrides"][0]["value"], "c++20");
+    assert_eq!(session.conformanceview.as_ref().unwrap().compilers[0].id, "clang1000");
+    assert_eq!((session.executors[0].arguments.as_str(), session.executors[0].wrap), ("1 2", Some(true)));
+    assert!(state.sessions[1].conformanceview.is_none());
+    // The conformance view's libraries reach each of its compilers
+    let requests = offline(LANGUAGES, COMPILERS).requests_from_state(&state);
+    let conformance = requests.iter().find(|r| r.compiler() == "clang1000").expect("a request for the conformance compiler");
+    assert_eq!(conformance.options().libraries, vec![crate::LibraryRef::new("fmt", "1010")]);
+    assert_eq!(state.trees[0].files[0].editor_id, 2);
+    assert_eq!(state.extra["layoutVersion"], 4);
+
+    // Unmodelled keys and conformanceview: false survive re-encoding
+    assert_eq!(serde_json::to_value(&state)?, original);
+
+    // And edits made in between are kept
+    let mut edited = state.clone();
+    edited.sessions[0].compilers[0].options = "-O3".to_string();
+    let reencoded : ClientState = serde_json::from_str(&serde_json::to_string(&edited)?)?;
+    assert_eq!(reencoded, edited);
+    assert_eq!(reencoded.sessions[0].compilers[0].options, "-O3");
+
+    // A minimal state decodes with everything else defaulted
+    let minimal : ClientState = serde_json::from_str(r#"{"sessions": [{"id": 1, "language": "c", "source": ""}]}"#)?;
+    assert!(minimal.sessions[0].compilers.is_empty() && minimal.trees.is_empty());
+
+    // Filters the crate does not model survive too
+    let filters = serde_json::json!({"intel": true, "verboseDemangling": true});
+    let compiler : crate::SessionCompiler = serde_json::from_value(serde_json::json!({"id": "g132", "filters": filters}))?;
+    assert_eq!(serde_json::to_value(&compiler)?["filters"], filters);
+    Ok(())
+}
+
+/// A layout with an editor, a compiler with an opt pane and llvm-mca, an executor and a
+/// conformance view, as Compiler Explorer puts it in the URL fragment
+const LAYOUT_FRAGMENT : &str = "version:4,g:!((l:'2',g:!((l:'5',h:codeEditor,i:(j:1,lang:c%2B%2B,source:'%23include+%3Ccstdio%3E%0Aint+main(int+argc,+char**+argv)+%7B+printf(%22%25d!!%5Cn%22,+argc)%3B+%7D',filename:'1',fontScale:14),o:'C%2B%2B+source+%231',n:'0',k:50),(l:'3',g:!((l:'4',s:0,g:!((l:'5',h:compiler,i:(j:1,compiler:g132,source:1,options:'-O2+-Wall',filters:(binary:'1',commentOnly:'0',demangle:'0',directives:'0',execute:'1',intel:'0',b:'0',libraryCode:'1',trim:'1'),libs:!((name:fmt,ver:'1010')),lang:c%2B%2B),n:'0'),(l:'5',h:opt,i:(j:1,source:1,compilerName:'x86-64+gcc+13.2',editorid:1)),(l:'5',h:tool,i:(compiler:1,editorid:1,toolId:llvm-mcatrunk,args:'-timeline',monacoStdin:'1')))),(l:'5',h:executor,i:(compiler:g132,source:1,options:'-O2',execArgs:'1+%22two+words%22',execStdin:___x,libs:!(),lang:c%2B%2B,compilationPanelShown:'0',compilerOutShown:'0',argsPanelShown:'0',stdinPanelShown:'0',wrap:'1')),(l:'5',h:conformance,i:(editorid:1,langId:c%2B%2B,libs:!(),compilers:!((compilerId:clang1000,options:'-std%3Dc%2B%2B20')))))))))";
+
+/// The same layout once Compiler Explorer compressed it
+const COMPRESSED_FRAGMENT : &str = "z:G4UwTgzglg9gdgLgCwBoDmCCEAKbAbBAcgCZD0tcDCBWMgCwQGMYATEAURagBcYwUoCbACsEARhR4AhnAyMApMQBCipSggwArmEYgiigMxQ4jPJrYBqeQYDCjCNy4xr7eQAYAgse4WAtlONsbwspMDRGFAtGOlCAKliQsOAASisAdiULAAcwbwAzbEViRWoWTEx5ahs4IsjQ8OTrTPk0gBEyPKg8EDgpXz1CMQ74bgBlRilu8SRklBgiG1VVCw1tXStiAyGUREI3MgBrBGo3WfwiAzIMHHPCJDIIBDdyG6paFAZmXyyu8AEhUQSL4/bpgBBoMQGYjqLQ6PQSGBZbiwOCPQgAWgA8sQLOiAOqTPAdLrccCPbAAI2MoQAnkRtl9%2BnBuJi4Hg6XsyGx/LIppyUFwwCBGMjQGj9igQAAPYWaUn0sjeEBUCUUogSvBQClgWk2VgDbbcXK%2BBWzTUUx43Xr9BB5XzcFCgMGDNxifbJM0yORLZSzXbulC3d4MREOwQicQwtbwlDA35gAByfQGUoAHAA2dHppAWcKMCyQgB0pElXF4uRY4g9gbe9AQvBgeH%2B2DjoMjIDLfCglYkDbwAElK3g8MBfOjfBMjZo4AcUPU0ejkf1NXAQGRfPApMxRo5jKaPWdax8ENLZeXm63wODIdDVnDI6GUQvsWRT4wPGE0WINsRuAB3GALAAsAWAgIpXxlRgdy4RAAH14KlSQtUtbBPVkJgfTUVspGReAAAUZGVUY6BgP9/TIS8wExOViNI8i50/AjVzwWiyPVB5dzgJiiJItj%2BT/HUsn3GsiGDJh4DyPgeV0ZsOx4Lse0kL1Bww5RVCQi0KFmSiUJbGBvnjFTTC9N03GeR94AXBwWGsVoFDU5RiHdA8XOSIA";
+
+#[test]
+fn decoding_links() -> Result<(), Box<dyn Error>> {
+    use crate::{ClientState, GodboltError};
+    let gbolt = offline(LANGUAGES, COMPILERS);
+
+    let state = ClientState::from_link(&format!("https://godbolt.org/#{}", LAYOUT_FRAGMENT))?;
+    assert_eq!(ClientState::from_link(&format!("https://godbolt.org/#{}", COMPRESSED_FRAGMENT))?, state);
+
+    let session = &state.sessions[0];
+    assert_eq!((session.id, session.language.as_str(), session.filename.as_deref()), (1, "c++", None));
+    assert_eq!(session.source, "#include <cstdio>\nint main(int argc, char** argv) { printf(\"%d!\\n\", argc); }");
+    let gcc = &session.compilers[0];
+    assert_eq!((gcc.id.as_str(), gcc.options.as_str()), ("g132", "-O2 -Wall"));
+    let filters = gcc.filters.clone().unwrap();
+    assert_eq!((filters.labels, filters.binary, filters.intel), (Some(true), Some(false), Some(true)));
+    assert_eq!(gcc.specialoutputs, vec!["opt"]);
+    assert_eq!((gcc.tools[0].id.as_str(), gcc.tools[0].args.as_str()), ("llvm-mcatrunk", "-timeline"));
+    assert_eq!(session.executors[0].stdin, "x");
+    assert!(session.executors[0].stdin_visible);
+    assert_eq!(session.conformanceview.as_ref().unwrap().compilers[0].id, "clang1000");
+
+    // Raw JSON is not mistaken for a link with a fragment
+    let json = ClientState::from_link(r##"{"sessions":[{"id":1,"language":"c++","source":"#include <cstdio>"}]}"##)?;
+    assert_eq!(json.sessions[0].source, "#include <cstdio>");
+
+    let (_, requests) = gbolt.decode_link(&format!("https://godbolt.org/#{}", LAYOUT_FRAGMENT))?;
+    let summary : Vec<(&str, &str)> = requests.iter().map(|r| (r.compiler(), r.options().user_arguments.as_str())).collect();
+    assert_eq!(summary, vec![("g132", "-O2 -Wall"), ("clang1000", "-std=c++20"), ("g132", "-O2")]);
+    assert_eq!(requests[0].options().libraries[0].id, "fmt");
+    assert_eq!(requests[0].options().tools[0].args, "-timeline");
+    let exec = &requests[2].options();
+    assert!(exec.compiler_options.executor_request);
+    assert_eq!(exec.execute_parameters.args, vec!["1", "two words"]);
+    assert_eq!(exec.execute_parameters.stdin, "x");
+    assert!(gbolt.validate(&requests[2]).is_ok());
+
+    // Links this crate builds decode back to the same requests, in every form
+    let c = gbolt.find_compiler_by_id("r1740").unwrap();
+    let options = RequestOptions { user_arguments: "-C opt-level=3".to_string(), ..Default::default() };
+    let link = gbolt.get_link(c, "fn main() {}", options.clone())?;
+    let encoded = Godbolt::get_base64(c, "fn main() {}", options)?;
+    for form in [link.as_str(), encoded.as_str()] {
+        let (state, requests) = gbolt.decode_link(form)?;
+        assert_eq!(state.sessions[0].language, "rust");
+        assert_eq!((requests[0].compiler(), requests[0].source()), ("r1740", "fn main() {}"));
+        assert_eq!(requests[0].options().user_arguments, "-C opt-level=3");
+    }
+
+    // Program arguments with spaces and quotes reach the executor unchanged
+    let args : Vec<String> = ["1", "hello world", "", "it's", "say \"hi\"", "a\"b'c"].iter().map(|a| a.to_string()).collect();
+    let options = RequestOptions { execute_parameters: ExecuteParameters { args: args.clone(), ..Default::default() }, ..Default::default() };
+    let state = crate::CompilationRequest::new(c, "fn main() {}", options).client_state();
+    let requests = gbolt.requests_from_state(&state);
+    assert_eq!(requests[1].options().execute_parameters.args, args);
+
+    // Compilers this instance does not know are kept, to be sent as is
+    let (_, requests) = gbolt.decode_link(r#"{"sessions":[{"id":1,"language":"c","source":"","compilers":[{"id":"cg999","options":""}]}]}"#)?;
+    assert_eq!(requests[0].compiler(), "cg999");
+
+    for bad in ["https://godbolt.org/z/abc123", "not base64!", "https://godbolt.org/#g:!((l:"] {
+        match ClientState::from_link(bad) {
+            Err(GodboltError::InvalidLink(_)) => {}
+            other => panic!("expected {} to be rejected, got {:?}", bad, other),
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn short_links() -> Result<(), Box<dyn Error>> {
+    use crate::{ClientState, CompilationRequest, GodboltError};
+    use crate::tests::server::{serve, Reply};
+    let server = serve("", |req| match (req.method.as_str(), req.path.as_str()) {
+        ("POST", "/api/shortener") => Reply::json(r#"{"url":"https://godbolt.org/z/G38YP7eW4"}"#),
+        ("GET", "/api/shortlinkinfo/G38YP7eW4") => Reply::json(CLIENT_STATE),
+        _ => Reply::status(404, "not found"),
+    }).await;
+    let mut gbolt = offline(LANGUAGES, COMPILERS);
+    gbolt.base_url = server.url.clone();
+    let c = gbolt.find_compiler_by_id("g132").unwrap();
+
+    let state = CompilationRequest::new(c, "int main() {}", RequestOptions::default()).client_state();
+    assert_eq!(gbolt.shorten(&state).await?, "https://godbolt.org/z/G38YP7eW4");
+    let posted : ClientState = serde_json::from_str(&server.requests()[0].body)?;
+    assert_eq!(posted, state);
+
+    let expected : ClientState = serde_json::from_str(CLIENT_STATE)?;
+    assert_eq!(gbolt.expand_short_link("G38YP7eW4").await?, expected);
+    assert_eq!(gbolt.expand_short_link("https://godbolt.org/z/G38YP7eW4/").await?, expected);
+
+    match gbolt.expand_short_link("https://godbolt.org/z/").await {
+        Err(GodboltError::InvalidLink(_)) => {}
+        other => panic!("expected an invalid link, got {:?}", other),
+    }
+    assert_eq!(gbolt.expand_short_link("missing").await.unwrap_err().status(), Some(404));
+    Ok(())
+}
+
+/// Assembly of `square` and a loop calling it, as Compiler Explorer returns it
+const ASM_RESPONSE : &str = r#"{
+    "code": 0, "stdout": [], "stderr": [], "asmSize": 512,
+    "asm": [
+        {"text": "square(int):", "source": null, "labels": []},
+        {"text": "        mov     eax, edi", "source": {"file": null, "line": 2, "column": 12}, "labels": []},
+        {"text": "        imul    eax, edi", "source": {"file": null, "line": 2, "column": 14}, "labels": []},
+        {"text": "        ret", "source": {"file": null, "line": 2}, "labels": []},
+        {"text": "main:", "source": null, "labels": []},
+        {"text": "        call    square(int)", "source": {"file": null, "line": 4, "column": 5},
+         "labels": [{"name": "square(int)", "range": {"startCol": 17, "endCol": 28}}]},
+        {"text": "        jmp     .L3", "source": {"file": "/usr/include/c++/13/bits/stl_algo.h", "line": 120, "mainsource": false},
+         "labels": [{"name": ".L3", "range": {"startCol": 17, "endCol": 20}}]},
+        {"text": ".L3:"},
+        {"text": "        ret", "source": {"file": "example.cpp", "line": 5, "mainsource": true},
+         "opcodes": ["c3"], "address": 4198705}
+    ],
+    "labelDefinitions": {"square(int)": 1, "main": 5}
+}"#;
+
+#[test]
+fn asm_source_mapping() -> Result<(), Box<dyn Error>> {
+    use crate::GodboltResponse;
+    let response : GodboltResponse = serde_json::from_str(ASM_RESPONSE)?;
+    let asm = response.asm_lines();
+    assert_eq!(asm.len(), 9);
+    assert_eq!(asm[1].source.as_ref().and_then(|s| s.column), Some(12));
+    assert_eq!((asm[8].opcodes.clone(), asm[8].address), (vec!["c3".to_string()], Some(4198705)));
+
+    // Lines from headers do not map to the main source
+    assert_eq!(response.asm_for_source_line(2), vec![1, 2, 3]);
+    assert_eq!(asm[6].source_line(), None);
+    assert_eq!(asm[8].source_line(), Some(5));
+    let map = response.source_map();
+    assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![2, 4, 5]);
+
+    // Labels are followed through labelDefinitions, or found by their definition line
+    let call = asm[5].label_at(20).expect("clicked on the call target");
+    assert_eq!(call.name, "square(int)");
+    assert_eq!(response.label_target(&call.name), Some(0));
+    assert!(asm[5].label_at(10).is_none());
+    assert_eq!(response.label_target(".L3"), Some(7));
+    assert_eq!(asm[4].label_definition(), Some("main"));
+    assert_eq!(asm[1].label_definition(), None);
+    let clang = crate::AsmResult { text: Some("main:                                   # @main".to_string()), ..Default::default() };
+    assert_eq!(clang.label_definition(), Some("main"));
+    assert_eq!(response.label_target("missing"), None);
+
+    // Minimal responses still parse
+    let bare : GodboltResponse = serde_json::from_str(r#"{"code": 0, "asm": [{"text": "ret"}]}"#)?;
+    assert!(bare.asm_lines()[0].labels.is_empty() && bare.label_definitions.is_empty());
+    Ok(())
+}
+
+#[test]
+fn interleaved_rendering() -> Result<(), Box<dyn Error>> {
+    use crate::{GodboltResponse, RenderOptions};
+    let response : GodboltResponse = serde_json::from_str(ASM_RESPONSE)?;
+    let source = "// square\nint square(int x) { return x * x; }\nint main() {\n    square(3);\n}";
+
+    let plain = response.render_interleaved(source, &RenderOptions::default());
+    let lines : Vec<&str> = plain.lines().collect();
+    assert_eq!(lines[..5], ["square(int):", "| int square(int x) { return x * x; }", "        mov     eax, edi", "        imul    eax, edi", "        ret"]);
+    // Assembly from headers and unmapped labels stand on their own
+    assert_eq!(lines[5..], ["main:", "|     square(3);", "        call    square(int)", "        jmp     .L3", ".L3:", "| }", "        ret"]);
+
+    let numbered = response.render_interleaved(source, &RenderOptions { line_numbers: true, width: Some(16), ..Default::default() });
+    assert!(numbered.contains("\n2 | int square(…\n"));
+    assert!(numbered.contains("\n        call   …\n"));
+    assert!(numbered.lines().all(|l| l.chars().count() <= 16));
+
+    let coloured = response.render_interleaved(source, &RenderOptions { colour: true, ..Default::default() });
+    assert!(coloured.contains("\x1b[33m| int square(int x) { return x * x; }\x1b[0m\n"));
+    assert!(coloured.contains("\x1b[33m        ret\x1b[0m\n"));
+    assert!(coloured.contains("\nmain:\n"));
+    Ok(())
+}
+
+fn asm_lines(text : &str) -> Vec<crate::AsmResult> {
+    text.lines().map(|l| crate::AsmResult { text: Some(l.to_string()), ..Default::default() }).collect()
+}
+
+#[test]
+fn asm_diffs() -> Result<(), Box<dyn Error>> {
+    use crate::{AsmDiff, DiffLine, FunctionStatus, DIFF_CONTEXT};
+    let left = asm_lines("        .text
+square(int):
+        mov     eax, edi  # x
+        imul    eax, edi
+        ret
+count(int):
+        test    edi, edi
+        jle     .L4
+.L3:
+        add     eax, 1
+        jmp     .L3
+.L4:
+        ret
+answer():
+        mov     w0, #41  // the answer
+        bl      401020 <puts@plt>
+        ret
+old():
+        ret");
+    let right = asm_lines("        .text
+square(int):
+        imul    edi, edi
+        mov     eax, edi
+        ret
+count(int):
+        .p2align 4
+        test    edi, edi
+        jle     .L7
+.L6:
+        add     eax, 1
+        jmp     .L6
+.L7:
+        ret
+answer():
+        mov     w0,   #42
+        bl      401040 <puts@plt>
+        ret
+new():
+        xor     eax, eax
+        ret");
+    let diff = AsmDiff::new(&left, &right);
+    let statuses : Vec<_> = diff.functions.iter().map(|f| (f.name.as_str(), f.status)).collect();
+    assert_eq!(statuses, vec![
+        ("square(int)", FunctionStatus::Changed), ("count(int)", FunctionStatus::Unchanged),
+        ("answer()", FunctionStatus::Changed), ("old()", FunctionStatus::Removed), ("new()", FunctionStatus::Added),
+    ]);
+    assert!(!diff.is_empty());
+
+    // Local labels are renumbered, comments and addresses dropped
+    let answer = diff.function("answer()").expect("answer is on both sides");
+    assert_eq!(answer.lines[0], DiffLine::Removed("mov w0, #41".to_string()));
+    assert_eq!(answer.lines[1], DiffLine::Added("mov w0, #42".to_string()));
+    assert_eq!(answer.lines[2], DiffLine::Same("bl <addr> <puts@plt>".to_string()));
+    assert_eq!((answer.summary.changed, answer.summary.added, answer.summary.removed, answer.summary.delta), (1, 0, 0, 0));
+
+    let summary = diff.summary();
+    assert_eq!((summary[1].left_instructions, summary[1].delta), (5, 0));
+    assert_eq!((summary[3].removed, summary[3].delta), (1, -1));
+    assert_eq!((summary[4].added, summary[4].right_instructions, summary[4].delta), (2, 2, 2));
+    let json = serde_json::to_value(&summary[0])?;
+    assert_eq!((json["status"].as_str(), json["added"].as_u64(), json["removed"].as_u64()), (Some("changed"), Some(1), Some(1)));
+
+    let unified = diff.unified(DIFF_CONTEXT);
+    assert!(unified.starts_with("--- a/square(int)\n+++ b/square(int)\n@@ -1,3 +1,3 @@\n+imul edi, edi\n mov eax, edi\n-imul eax, edi\n ret\n"));
+    assert!(unified.contains("--- a/old()\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-ret\n"));
+    assert!(unified.contains("--- /dev/null\n+++ b/new()\n@@ -0,0 +1,2 @@\n+xor eax, eax\n+ret\n"));
+    assert!(!unified.contains("count(int)"));
+    // Demangled names with several arguments hold spaces but still start functions
+    let left = asm_lines("f():\n        ret\nsum(int*, int):\n        mov     eax, esi\n        ret");
+    let right = asm_lines("f():\n        ret\nsum(int*, int):\n        lea     eax, [rsi+1]\n        ret");
+    let diff = AsmDiff::new(&left, &right);
+    assert_eq!(diff.functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["f()", "sum(int*, int)"]);
+    assert_eq!(diff.function("f()").map(|f| (f.status, f.summary.left_instructions)), Some((FunctionStatus::Unchanged, 1)));
+    assert_eq!(diff.function("sum(int*, int)").map(|f| (f.status, f.summary.changed)), Some((FunctionStatus::Changed, 1)));
+    // Large immediates are values, not addresses
+    let left = asm_lines("div10(unsigned int):\n        mov     eax, 1000000\n        imul    rax, rax, 1374389535\n        call    0x401020");
+    let right = asm_lines("div10(unsigned int):\n        mov     eax, 2000000\n        imul    rax, rax, 1717986919\n        call    0x401040");
+    let diff = AsmDiff::new(&left, &right);
+    assert!(!diff.is_empty());
+    let div = diff.function("div10(unsigned int)").expect("div10 is on both sides");
+    assert_eq!((div.status, div.summary.changed), (FunctionStatus::Changed, 2));
+    assert_eq!(div.lines[4], DiffLine::Same("call <addr>".to_string()));
+
+    // Clang comments its labels
+    let left = asm_lines("add:                                    # @add
+        lea     eax, [rdi + rsi]
+        ret
+sum(int*, int):                              # @sum(int*, int)
+        xor     eax, eax
+.LBB1_2:                                # =>This Inner Loop Header: Depth=1
+        add     eax, dword ptr [rdi]
+        jne     .LBB1_2
+        ret");
+    let right = asm_lines(&left.iter().map(|l| l.text().replace("lea     eax, [rdi + rsi]", "lea     eax, [rsi + rdi]")).collect::<Vec<_>>().join("\n"));
+    let diff = AsmDiff::new(&left, &right);
+    assert_eq!(diff.functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["add", "sum(int*, int)"]);
+    assert_eq!(diff.function("add").map(|f| (f.status, f.summary.changed)), Some((FunctionStatus::Changed, 1)));
+    let sum = diff.function("sum(int*, int)").expect("sum is on both sides");
+    assert_eq!((sum.status, sum.summary.left_instructions), (FunctionStatus::Unchanged, 4));
+    assert_eq!(sum.lines[1], DiffLine::Same(".L0:".to_string()));
+
+    assert!(AsmDiff::new(&left, &left).is_empty());
+    Ok(())
+}
+
+#[test]
+fn control_flow_graphs() -> Result<(), Box<dyn Error>> {
+    use crate::{Arch, Cfg, CfgEdge, EdgeKind};
+    let x86 = asm_lines("sum(int*, int):
+        xor     eax, eax  # total
+        test    esi, esi
+        jle     .L4
+        .p2align 4
+.L3:
+.L5:
+        add     eax, DWORD PTR [rdi]
+        add     rdi, 4
+        dec     esi
+        jne     .L3
+        ret
+.L4:
+        ret
+tail(int):
+        add     edi, 1
+        jmp     sum(int*, int)
+spin():
+.L8:
+        jmp     .L8");
+    assert_eq!(Arch::detect(&x86), Arch::X86_64);
+    let cfg = Cfg::new(&x86, Arch::X86_64);
+    assert_eq!(cfg.functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["sum(int*, int)", "tail(int)", "spin()"]);
+
+    let sum = cfg.function("sum(int*, int)").expect("sum is in the listing");
+    let blocks : Vec<_> = sum.blocks.iter().map(|b| (b.label.as_deref(), b.instructions.len())).collect();
+    assert_eq!(blocks, vec![(None, 3), (Some(".L3"), 4), (None, 1), (Some(".L4"), 1)]);
+    assert_eq!(sum.blocks[0].instructions[0], "xor eax, eax");
+    assert_eq!((sum.blocks[1].start, sum.blocks[1].end), (5, 11));
+    assert_eq!(sum.edges, vec![
+        CfgEdge { from: 0, to: 3, kind: EdgeKind::Taken },
+        CfgEdge { from: 0, to: 1, kind: EdgeKind::NotTaken },
+        CfgEdge { from: 1, to: 1, kind: EdgeKind::Taken },
+        CfgEdge { from: 1, to: 2, kind: EdgeKind::NotTaken },
+    ]);
+    // The second label names the same block
+    assert_eq!(sum.block(".L3").map(|b| b.id), Some(1));
+    assert_eq!(sum.successors(2), Vec::<usize>::new());
+    // Tail calls leave the function
+    assert!(cfg.function("tail(int)").is_some_and(|f| f.blocks.len() == 1 && f.edges.is_empty()));
+    assert_eq!(cfg.function("spin()").map(|f| f.successors(0)), Some(vec![0]));
+
+    // Clang comments its labels, function and local alike
+    let clang = asm_lines("square(int):                        # @square(int)
+        mov     eax, edi
+        imul    eax, edi
+        ret
+sum(int*, int):                          # @sum(int*, int)
+        test    esi, esi
+        jle     .LBB1_1
+        mov     ecx, esi
+        xor     eax, eax
+.LBB1_4:                                # =>This Inner Loop Header: Depth=1
+        add     eax, dword ptr [rdi]
+        add     rdi, 4
+        dec     rcx
+        jne     .LBB1_4
+        ret
+.LBB1_1:
+        xor     eax, eax
+        ret");
+    let graph = Cfg::new(&clang, Arch::X86_64);
+    assert_eq!(graph.functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["square(int)", "sum(int*, int)"]);
+    let sum = graph.function("sum(int*, int)").expect("sum is in the listing");
+    assert_eq!(sum.block(".LBB1_4").map(|b| (b.id, b.instructions.len())), Some((2, 4)));
+    assert_eq!(sum.edges, vec![
+        CfgEdge { from: 0, to: 4, kind: EdgeKind::Taken },
+        CfgEdge { from: 0, to: 1, kind: EdgeKind::NotTaken },
+        CfgEdge { from: 1, to: 2, kind: EdgeKind::Fallthrough },
+        CfgEdge { from: 2, to: 2, kind: EdgeKind::Taken },
+        CfgEdge { from: 2, to: 3, kind: EdgeKind::NotTaken },
+    ]);
+
+    let aarch64 = asm_lines("count(unsigned int):
+        mov     w1, w0
+        mov     w0, 0
+        cbz     w1, .L1
+.L3:
+        and     w2, w1, 1  // low bit
+        add     w0, w0, w2
+        lsr     w1, w1, #1
+        cbnz    w1, .L3
+.L1:
+        ret");
+    assert_eq!(Arch::detect(&aarch64), Arch::AArch64);
+    assert_eq!(Arch::from_instruction_set("aarch64"), Some(Arch::AArch64));
+    assert_eq!(Arch::from_instruction_set("amd64"), Some(Arch::X86_64));
+    let cfg = Cfg::new(&aarch64, Arch::AArch64);
+    let count = &cfg.functions[0];
+    assert_eq!(count.blocks.len(), 3);
+    assert_eq!(count.blocks[1].instructions[2], "lsr w1, w1, #1");
+    assert_eq!(count.successors(0), vec![2, 1]);
+    assert_eq!(count.successors(1), vec![1, 2]);
+
+    // Mach-O local labels neither start functions nor leave empty blocks
+    let macho = asm_lines("_main:\n        cbz     w0, LBB0_2\n        mov     w0, #1\nLBB0_2:\n        ret\nLfunc_end0:\nl_.str:\n        .asciz  \"hi\"");
+    let graph = Cfg::new(&macho, Arch::AArch64);
+    assert_eq!(graph.functions.len(), 1);
+    assert_eq!(graph.functions[0].blocks.len(), 3);
+    assert_eq!(graph.functions[0].successors(0), vec![2, 1]);
+    assert_eq!(crate::AsmDiff::new(&macho, &macho).functions.len(), 1);
+
+    let dot = cfg.to_dot();
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("label=\"count(unsigned int)\";"));
+    assert!(dot.contains("f0b1 [label=\".L3:\\l    and w2, w1, 1\\l"));
+    assert!(dot.contains("f0b0 -> f0b2 [color=green];"));
+    assert!(dot.contains("f0b0 -> f0b1 [color=red];"));
+
+    let json : serde_json::Value = serde_json::from_str(&cfg.to_json()?)?;
+    assert_eq!(json["arch"], "aarch64");
+    assert_eq!(json["functions"][0]["edges"][1], serde_json::json!({"from": 0, "to": 1, "kind": "nottaken"}));
+    assert_eq!(json["functions"][0]["blocks"][2]["label"], ".L1");
+    Ok(())
+}
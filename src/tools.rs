@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{StdErrResult, StdOutResult, TagResult};
+
+/// A tool to run alongside the compiler (i.e. clang-tidy, llvm-mca, pahole), see [`Compiler::tools`](crate::Compiler::tools)
+#[derive(Clone, Debug, Serialize, Default, PartialEq, Eq)]
+pub struct ToolRequest {
+    /// Tool id, a key of [`Compiler::tools`](crate::Compiler::tools) (i.e. llvm-mcatrunk)
+    pub id : String,
+    /// Arguments to pass to the tool, split like a shell would
+    pub args : String,
+    /// Input for tools that read stdin
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub stdin : String,
+}
+
+impl ToolRequest {
+    pub fn new(id : &str, args : &str) -> Self {
+        ToolRequest {
+            id: id.to_string(),
+            args: args.to_string(),
+            stdin: String::new(),
+        }
+    }
+
+    pub fn with_stdin(mut self, stdin : &str) -> Self {
+        self.stdin = stdin.to_string();
+        self
+    }
+}
+
+/// Output of a tool that ran alongside the compiler
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct ToolResult {
+    /// Tool id
+    pub id : String,
+    /// Tool display name
+    pub name : Option<String>,
+    /// Exit code of the tool
+    pub code : i32,
+    #[serde(default)]
+    pub stdout : Vec<StdOutResult>,
+    #[serde(default)]
+    pub stderr : Vec<StdErrResult>,
+}
+
+impl ToolResult {
+    /// Whether the tool exited successfully
+    pub fn succeeded(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Standard output, one line per entry, joined back together
+    pub fn stdout_text(&self) -> String {
+        self.stdout.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Standard error, joined like [`ToolResult::stdout_text`]
+    pub fn stderr_text(&self) -> String {
+        self.stderr.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Diagnostics the tool tagged with a source location, from stdout then stderr
+    pub fn diagnostics(&self) -> Vec<&TagResult> {
+        self.stdout.iter().filter_map(|l| l.tag.as_ref())
+            .chain(self.stderr.iter().filter_map(|l| l.tag.as_ref()))
+            .collect()
+    }
+}
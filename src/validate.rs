@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::{CompilationRequest, Compiler, Library, CMAKELISTS};
+
+/// A problem found by checking a request against a compiler's capabilities.
+/// Capabilities the compiler's metadata does not mention are assumed to be present.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// No compiler with this id is cached
+    UnknownCompiler(String),
+    /// No language with this id, name or extension is cached
+    UnknownLanguage(String),
+    /// Execution was requested but the compiler cannot run programs
+    ExecutionUnsupported(String),
+    /// The binary filter was requested but the compiler cannot disassemble binaries
+    BinaryUnsupported(String),
+    /// The binary object filter was requested but the compiler cannot disassemble objects
+    BinaryObjectUnsupported(String),
+    /// Intel syntax was requested but the compiler does not support it
+    IntelUnsupported(String),
+    /// Demangling was requested but the compiler does not support it
+    DemangleUnsupported(String),
+    /// A filter was enabled that the compiler ignores
+    FilterDisabled {
+        /// Compiler id
+        compiler : String,
+        /// Filter name, as Compiler Explorer spells it (i.e. commentOnly)
+        filter : String,
+    },
+    /// No library with this id exists for the language
+    UnknownLibrary(String),
+    /// The library exists, but not in this version
+    UnknownLibraryVersion {
+        /// Library id
+        library : String,
+        /// Requested version id
+        version : String,
+    },
+    /// The library exists, but cannot be used with this compiler
+    LibraryUnsupported {
+        /// Compiler id
+        compiler : String,
+        /// Library id
+        library : String,
+    },
+    /// The tool is not available with this compiler
+    ToolUnavailable {
+        /// Compiler id
+        compiler : String,
+        /// Tool id
+        tool : String,
+    },
+    /// Input was given to a tool that does not read stdin
+    ToolStdinIgnored(String),
+    /// A file the request needs is missing (i.e. a CMake project without CMakeLists.txt)
+    MissingFile(String),
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::UnknownCompiler(id) => write!(f, "unknown compiler `{}`", id),
+            ValidationIssue::UnknownLanguage(id) => write!(f, "unknown language `{}`", id),
+            ValidationIssue::ExecutionUnsupported(id) => write!(f, "`{}` cannot execute programs", id),
+            ValidationIssue::BinaryUnsupported(id) => write!(f, "`{}` does not support binary output", id),
+            ValidationIssue::BinaryObjectUnsupported(id) => write!(f, "`{}` does not support binary object output", id),
+            ValidationIssue::IntelUnsupported(id) => write!(f, "`{}` does not support Intel syntax", id),
+            ValidationIssue::DemangleUnsupported(id) => write!(f, "`{}` does not support demangling", id),
+            ValidationIssue::FilterDisabled { compiler, filter } => write!(f, "the `{}` filter has no effect with `{}`", filter, compiler),
+            ValidationIssue::UnknownLibrary(id) => write!(f, "unknown library `{}`", id),
+            ValidationIssue::UnknownLibraryVersion { library, version } => write!(f, "`{}` has no version `{}`", library, version),
+            ValidationIssue::LibraryUnsupported { compiler, library } => write!(f, "`{}` cannot be used with `{}`", library, compiler),
+            ValidationIssue::ToolUnavailable { compiler, tool } => write!(f, "`{}` is not available with `{}`", tool, compiler),
+            ValidationIssue::ToolStdinIgnored(tool) => write!(f, "`{}` does not read stdin", tool),
+            ValidationIssue::MissingFile(name) => write!(f, "missing `{}`", name),
+        }
+    }
+}
+
+/// Outcome of checking a request before sending it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Problems that would make the request fail or behave unexpectedly
+    pub errors : Vec<ValidationIssue>,
+    /// Options that will be silently ignored
+    pub warnings : Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the request can be sent
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Checks a request against the capabilities listed in `compiler`'s metadata, and
+/// its libraries against `libraries` unless that is empty (not loaded)
+pub(crate) fn check(compiler : &Compiler, libraries : &[Library], request : &CompilationRequest) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let id = &compiler.id;
+    let options = &request.options;
+    let filters = &options.filters;
+
+    if request.cmake && request.main_filename.as_deref() != Some(CMAKELISTS) {
+        report.errors.push(ValidationIssue::MissingFile(CMAKELISTS.to_string()));
+    }
+
+    let executes = options.compiler_options.executor_request || filters.execute == Some(true);
+    if executes && compiler.supports_execute == Some(false) {
+        report.errors.push(ValidationIssue::ExecutionUnsupported(id.clone()));
+    }
+    if filters.binary == Some(true) && compiler.supports_binary == Some(false) {
+        report.errors.push(ValidationIssue::BinaryUnsupported(id.clone()));
+    }
+    if filters.binary_object == Some(true) && compiler.supports_binary_object == Some(false) {
+        report.errors.push(ValidationIssue::BinaryObjectUnsupported(id.clone()));
+    }
+    if filters.intel == Some(true) && compiler.supports_intel == Some(false) {
+        report.warnings.push(ValidationIssue::IntelUnsupported(id.clone()));
+    }
+    if filters.demangle == Some(true) && compiler.supports_demangle == Some(false) {
+        report.warnings.push(ValidationIssue::DemangleUnsupported(id.clone()));
+    }
+
+    let enabled = [
+        ("binary", filters.binary),
+        ("binaryObject", filters.binary_object),
+        ("commentOnly", filters.comment_only),
+        ("debugCalls", filters.debug_calls),
+        ("demangle", filters.demangle),
+        ("directives", filters.directives),
+        ("execute", filters.execute),
+        ("intel", filters.intel),
+        ("labels", filters.labels),
+        ("libraryCode", filters.library_code),
+        ("trim", filters.trim),
+    ];
+    for (filter, value) in enabled {
+        if value == Some(true) && compiler.disabled_filters.iter().any(|f| f == filter) {
+            report.warnings.push(ValidationIssue::FilterDisabled {
+                compiler: id.clone(),
+                filter: filter.to_string(),
+            });
+        }
+    }
+
+    for lib in &options.libraries {
+        if !compiler.libs.is_empty() && !compiler.libs.contains(&lib.id) {
+            report.errors.push(ValidationIssue::LibraryUnsupported {
+                compiler: id.clone(),
+                library: lib.id.clone(),
+            });
+        }
+        if libraries.is_empty() {
+            continue;
+        }
+        match libraries.iter().find(|l| l.id == lib.id) {
+            None => report.errors.push(ValidationIssue::UnknownLibrary(lib.id.clone())),
+            Some(known) if !known.versions.iter().any(|v| v.id == lib.version) => {
+                report.errors.push(ValidationIssue::UnknownLibraryVersion {
+                    library: lib.id.clone(),
+                    version: lib.version.clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Compilers listed without metadata have no tools map; assume the tools are there
+    for tool in &options.tools {
+        match compiler.tools.get(&tool.id) {
+            None if !compiler.tools.is_empty() => report.errors.push(ValidationIssue::ToolUnavailable {
+                compiler: id.clone(),
+                tool: tool.id.clone(),
+            }),
+            Some(known) if !tool.stdin.is_empty() && known.allow_stdin == Some(false) => {
+                report.warnings.push(ValidationIssue::ToolStdinIgnored(tool.id.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    report
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+use crate::Compiler;
+
+/// A numeric compiler version. Missing components are zero, so "13.2" is 13.2.0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Version {
+    pub major : u32,
+    pub minor : u32,
+    pub patch : u32,
+}
+
+impl Version {
+    pub fn new(major : u32, minor : u32, patch : u32) -> Self {
+        Version { major, minor, patch }
+    }
+
+    /// Parses the leading numeric components of strings like "13.2", "v19.38" or "1.74.0-nightly"
+    pub fn parse(s : &str) -> Option<Self> {
+        let s = s.trim().trim_start_matches(['v', 'V']);
+        let mut parts = [0u32; 3];
+        let mut found = 0;
+        for (i, part) in s.split('.').take(3).enumerate() {
+            let digits : String = part.chars().take_while(char::is_ascii_digit).collect();
+            if digits.is_empty() {
+                break;
+            }
+            parts[i] = digits.parse().ok()?;
+            found += 1;
+            if digits.len() != part.len() {
+                break;
+            }
+        }
+        if found == 0 {
+            return None;
+        }
+        Some(Version::new(parts[0], parts[1], parts[2]))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// What a compiler is, as far as its metadata and display name tell
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CompilerVersion {
+    /// Normalized family name (i.e. gcc, clang, msvc, rustc)
+    pub family : String,
+    /// Release version, absent for builds that only have a branch name
+    pub version : Option<Version>,
+    /// Whether this is a trunk, nightly or snapshot build
+    pub trunk : bool,
+    /// Normalized target architecture (i.e. x86_64, aarch64, riscv64)
+    pub arch : Option<String>,
+}
+
+/// Names compilers go by in display names, and the family each belongs to
+const FAMILIES : &[(&str, &str)] = &[
+    ("clang", "clang"),
+    ("clang++", "clang"),
+    ("gcc", "gcc"),
+    ("g++", "gcc"),
+    ("gfortran", "gcc"),
+    ("msvc", "msvc"),
+    ("vc", "msvc"),
+    ("cl", "msvc"),
+    ("icc", "icc"),
+    ("icx", "icx"),
+    ("rustc", "rustc"),
+    ("zig", "zig"),
+    ("nvcc", "nvcc"),
+    ("nvc++", "nvc++"),
+    ("ldc", "ldc"),
+    ("dmd", "dmd"),
+    ("gdc", "gcc"),
+    ("gccgo", "gcc"),
+    ("swiftc", "swiftc"),
+    ("ghc", "ghc"),
+    ("tcc", "tcc"),
+];
+
+/// Markers of unreleased builds
+const TRUNK : &[&str] = &["trunk", "nightly", "snapshot", "tip", "head", "master"];
+
+/// Normalizes a family name as users type it, so "g++" and "GCC" both mean gcc
+pub(crate) fn normalize_family(name : &str) -> String {
+    let name = name.trim().to_lowercase();
+    FAMILIES.iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, family)| family.to_string())
+        .unwrap_or(name)
+}
+
+/// Normalizes an architecture name (amd64, x86-64 and x64 are all x86_64)
+pub(crate) fn normalize_arch(name : &str) -> Option<String> {
+    let arch = match name.trim().to_lowercase().as_str() {
+        "amd64" | "x86-64" | "x86_64" | "x64" => "x86_64",
+        "x86" | "i386" | "i686" | "386" => "x86",
+        "aarch64" | "arm64" | "armv8-a" | "arm64ec" => "aarch64",
+        "arm" | "arm32" | "armv7-a" | "armv7" | "thumb" => "arm",
+        "riscv64" | "risc-v64" | "rv64gc" | "rv64" => "riscv64",
+        "riscv32" | "risc-v32" | "rv32gc" | "rv32" => "riscv32",
+        "mips" | "mips32" => "mips",
+        "mips64" => "mips64",
+        "power" | "ppc" | "powerpc" => "powerpc",
+        "power64" | "ppc64" | "powerpc64" => "powerpc64",
+        "power64le" | "ppc64le" | "powerpc64le" => "powerpc64le",
+        "s390x" => "s390x",
+        "wasm32" => "wasm32",
+        "wasm64" => "wasm64",
+        "avr" => "avr",
+        "msp430" => "msp430",
+        "6502" => "6502",
+        "sparc" => "sparc",
+        "sparc64" => "sparc64",
+        "loongarch64" => "loongarch64",
+        "kvx" => "kvx",
+        _ => return None,
+    };
+    Some(arch.to_string())
+}
+
+/// Derives a [`CompilerVersion`] from metadata, falling back to the display name
+pub(crate) fn parse(compiler : &Compiler) -> CompilerVersion {
+    let name = compiler.name.to_lowercase();
+    let words : Vec<&str> = name.split(|c : char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    // Some names split the architecture over two words, as in "RISC-V (64-bits)"
+    let arch = compiler.instruction_set.as_deref()
+        .and_then(normalize_arch)
+        .or_else(|| words.iter().find_map(|w| normalize_arch(w)))
+        .or_else(|| words.windows(2).find_map(|w| normalize_arch(&format!("{}{}", w[0], w[1].trim_end_matches("-bits")))));
+
+    let family = match compiler.compiler_type.as_deref().map(str::to_lowercase).as_deref() {
+        Some("gcc") => Some("gcc".to_string()),
+        Some("clang") => Some("clang".to_string()),
+        Some("win32-vc") => Some("msvc".to_string()),
+        Some("rust") => Some("rustc".to_string()),
+        _ => None,
+    };
+    let family = family
+        .or_else(|| words.iter().find_map(|w| FAMILIES.iter().find(|(alias, _)| alias == w).map(|(_, f)| f.to_string())))
+        .or_else(|| words.iter()
+            .find(|w| w.chars().any(char::is_alphabetic) && normalize_arch(w).is_none() && !TRUNK.contains(w))
+            .map(|w| w.to_string()))
+        .unwrap_or_default();
+
+    let semver = compiler.semver.as_deref().unwrap_or("").to_lowercase();
+    let trunk = TRUNK.iter().any(|t| semver.contains(t) || words.contains(t));
+
+    // Names can hold other numbers before the version ("RISC-V (64-bits) gcc 14.2.0"), so
+    // the version is the word after the family, or else the last version-like word
+    let family_at = words.iter().position(|w| normalize_family(w) == family);
+    let is_version = |w : &str| normalize_arch(w).is_none() && !w.ends_with("-bits");
+    let version = Version::parse(&semver)
+        .or_else(|| family_at.and_then(|i| words.get(i + 1)).filter(|w| is_version(w)).and_then(|w| Version::parse(w)))
+        .or_else(|| words.iter().rev().filter(|w| is_version(w)).find_map(|w| Version::parse(w)));
+
+    CompilerVersion { family, version, trunk, arch }
+}